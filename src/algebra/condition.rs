@@ -0,0 +1,410 @@
+use std::{
+    collections::HashMap,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use crate::prelude::Error;
+
+/// An expression deciding whether a workflow or a step runs, e.g.
+/// `args.target == 'production' and not env.CI`. It supports:
+/// * `args.<name>`, `env.<name>`, strings in single or double quotes, numbers, `true` and `false`
+/// * `==`, `!=`, `<`, `<=`, `>`, `>=`, comparing numbers as numbers and anything else as text
+/// * `and`, `or`, `not` and parentheses
+/// * `exists(<path>)`, whether a file or directory exists, relative to where the workflow runs
+///
+/// On its own a value holds when it is set and neither empty nor `false`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition(Expression);
+
+/// What a condition is evaluated against.
+pub struct Scope<'a> {
+    args: &'a HashMap<String, String>,
+    env: HashMap<String, String>,
+    cwd: PathBuf,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(
+        args: &'a HashMap<String, String>,
+        env: HashMap<String, String>,
+        cwd: &Path,
+    ) -> Self {
+        Self {
+            args,
+            env,
+            cwd: cwd.to_path_buf(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Literal(String),
+    Arg(String),
+    Env(String),
+    Exists(Box<Expression>),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Compare(Box<Expression>, Operator, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Operator(Operator),
+    Dot,
+    Open,
+    Close,
+}
+
+impl Condition {
+    /// Parse the given expression.
+    ///
+    /// # Returns
+    /// * The `Condition` or an `Error` telling what is wrong with the expression
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let invalid = |message: String| {
+            Error::ParseError(Some(
+                format!("Invalid condition `{}`: {}", source, message).into(),
+            ))
+        };
+
+        let tokens = tokenize(source).map_err(invalid)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expression = parser.or().map_err(invalid)?;
+        match parser.peek() {
+            Some(token) => Err(invalid(format!("unexpected {}", describe(token)))),
+            None => Ok(Self(expression)),
+        }
+    }
+
+    /// Whether the condition holds in the given scope.
+    pub fn evaluate(&self, scope: &Scope) -> bool {
+        is_true(&evaluate(&self.0, scope))
+    }
+}
+
+/// The value of an expression, `None` when it refers to something that is not set.
+fn evaluate(expression: &Expression, scope: &Scope) -> Option<String> {
+    let boolean = |value: bool| Some(value.to_string());
+
+    match expression {
+        Expression::Literal(value) => Some(value.clone()),
+        Expression::Arg(name) => scope.args.get(name).cloned(),
+        Expression::Env(name) => scope.env.get(name).cloned(),
+        Expression::Exists(path) => {
+            boolean(evaluate(path, scope).is_some_and(|path| scope.cwd.join(path).exists()))
+        }
+        Expression::Not(expression) => boolean(!is_true(&evaluate(expression, scope))),
+        Expression::And(left, right) => {
+            boolean(is_true(&evaluate(left, scope)) && is_true(&evaluate(right, scope)))
+        }
+        Expression::Or(left, right) => {
+            boolean(is_true(&evaluate(left, scope)) || is_true(&evaluate(right, scope)))
+        }
+        Expression::Compare(left, operator, right) => {
+            // Something that is not set compares as empty
+            let left = evaluate(left, scope).unwrap_or_default();
+            let right = evaluate(right, scope).unwrap_or_default();
+            let ordering = match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+                (Ok(left), Ok(right)) => left.partial_cmp(&right),
+                _ => Some(left.cmp(&right)),
+            };
+
+            boolean(ordering.is_some_and(|ordering| match operator {
+                Operator::Equal => ordering.is_eq(),
+                Operator::NotEqual => ordering.is_ne(),
+                Operator::Less => ordering.is_lt(),
+                Operator::LessOrEqual => ordering.is_le(),
+                Operator::Greater => ordering.is_gt(),
+                Operator::GreaterOrEqual => ordering.is_ge(),
+            }))
+        }
+    }
+}
+
+fn is_true(value: &Option<String>) -> bool {
+    value
+        .as_deref()
+        .is_some_and(|value| !value.is_empty() && value != "false")
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(&char) = chars.peek() {
+        match char {
+            char if char.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '.' => {
+                chars.next();
+                tokens.push(match char {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Dot,
+                });
+            }
+            '\'' | '"' => {
+                chars.next();
+                tokens.push(Token::Text(quoted(&mut chars, char)?));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let is_equal = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Operator(match (char, is_equal) {
+                    ('=', true) => Operator::Equal,
+                    ('!', true) => Operator::NotEqual,
+                    ('<', false) => Operator::Less,
+                    ('<', true) => Operator::LessOrEqual,
+                    ('>', false) => Operator::Greater,
+                    ('>', true) => Operator::GreaterOrEqual,
+                    _ => return Err(format!("unexpected `{}`, use `==` or `!=`", char)),
+                }));
+            }
+            char if char.is_alphanumeric() || char == '_' || char == '-' => {
+                let mut word = String::new();
+                while let Some(char) =
+                    chars.next_if(|char| char.is_alphanumeric() || *char == '_' || *char == '-')
+                {
+                    word.push(char);
+                }
+                // The decimals of a number are not a path to a value
+                if word
+                    .chars()
+                    .all(|char| char.is_ascii_digit() || char == '-')
+                {
+                    if let Some(dot) = chars.next_if_eq(&'.') {
+                        word.push(dot);
+                        while let Some(char) = chars.next_if(char::is_ascii_digit) {
+                            word.push(char);
+                        }
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            char => return Err(format!("unexpected `{}`", char)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String, String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(char) => text.push(char),
+                None => break,
+            },
+            Some(char) if char == quote => return Ok(text),
+            Some(char) => text.push(char),
+            None => break,
+        }
+    }
+
+    Err(format!("missing the closing {}", quote))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("`{}`", word),
+        Token::Text(text) => format!("'{}'", text),
+        Token::Operator(_) => "comparison".to_string(),
+        Token::Dot => "`.`".to_string(),
+        Token::Open => "`(`".to_string(),
+        Token::Close => "`)`".to_string(),
+    }
+}
+
+/// A recursive descent parser, from the loosest operator to the tightest one.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_word(&self, expected: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == expected)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!(
+                "expected {} instead of {}",
+                describe(&expected),
+                describe(&token)
+            )),
+            None => Err(format!("expected {} at the end", describe(&expected))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.and()?;
+        while self.is_word("or") {
+            self.next();
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.not()?;
+        while self.is_word("and") {
+            self.next();
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<Expression, String> {
+        if self.is_word("not") {
+            self.next();
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expression, String> {
+        let left = self.value()?;
+        match self.peek() {
+            Some(Token::Operator(operator)) => {
+                let operator = *operator;
+                self.next();
+                Ok(Expression::Compare(
+                    Box::new(left),
+                    operator,
+                    Box::new(self.value()?),
+                ))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn value(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expression = self.or()?;
+                self.expect(Token::Close)?;
+                Ok(expression)
+            }
+            Some(Token::Text(text)) => Ok(Expression::Literal(text)),
+            Some(Token::Word(word)) if word == "exists" => {
+                self.expect(Token::Open)?;
+                let path = self.or()?;
+                self.expect(Token::Close)?;
+                Ok(Expression::Exists(Box::new(path)))
+            }
+            Some(Token::Word(word)) if word == "args" || word == "env" => {
+                self.expect(Token::Dot)?;
+                let name = match self.next() {
+                    Some(Token::Word(name)) => name,
+                    _ => return Err(format!("expected a name after `{}.`", word)),
+                };
+                Ok(match word.as_str() {
+                    "args" => Expression::Arg(name),
+                    _ => Expression::Env(name),
+                })
+            }
+            Some(Token::Word(word))
+                if word == "true" || word == "false" || word.parse::<f64>().is_ok() =>
+            {
+                Ok(Expression::Literal(word))
+            }
+            Some(Token::Word(word)) => Err(format!(
+                "unknown `{}`, use `args.{}`, `env.{}` or quote it",
+                word, word, word
+            )),
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holds(condition: &str) -> bool {
+        let args = HashMap::from([
+            ("target".to_string(), "production".to_string()),
+            ("replicas".to_string(), "10".to_string()),
+            ("dry_run".to_string(), "false".to_string()),
+        ]);
+        let env = HashMap::from([("CI".to_string(), "true".to_string())]);
+        let scope = Scope::new(&args, env, Path::new(env!("CARGO_MANIFEST_DIR")));
+
+        Condition::parse(condition).unwrap().evaluate(&scope)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(holds("args.target == 'production'"));
+        assert!(holds("args.target != \"staging\""));
+        assert!(holds("args.replicas > 9"));
+        assert!(!holds("args.replicas <= 9.5"));
+        assert!(holds("args.unknown == ''"));
+    }
+
+    #[test]
+    fn test_logic() {
+        assert!(holds("env.CI and not args.dry_run"));
+        assert!(holds("env.MISSING or args.target == 'production'"));
+        assert!(!holds("not (env.CI or env.MISSING)"));
+        assert!(!holds("args.dry_run"));
+    }
+
+    #[test]
+    fn test_exists() {
+        assert!(holds("exists('Cargo.toml')"));
+        assert!(!holds("exists('missing.toml')"));
+    }
+
+    #[test]
+    fn test_invalid_conditions() {
+        for condition in [
+            "args.target = 'production'",
+            "target == 'production'",
+            "args.target == 'production",
+            "(env.CI",
+            "env.CI env.HOME",
+            "",
+        ] {
+            assert!(
+                matches!(Condition::parse(condition), Err(Error::ParseError(_))),
+                "{}",
+                condition
+            );
+        }
+    }
+}
@@ -1,15 +1,32 @@
 use std::{collections::HashMap, str::FromStr};
 
-use crate::prelude::Error;
+use regex::Regex;
+
+use crate::prelude::{Error, WorkflowShell};
+
+/// The key of the patterns of dangerous commands, which can be repeated.
+const DANGEROUS_PATTERN: &str = "dangerous_pattern";
 
 pub struct Configuration {
     workflow_dir: String,
+    shell: Option<WorkflowShell>,
+    dangerous_patterns: Vec<Regex>,
 }
 
 impl Configuration {
     pub fn workflow_dir(&self) -> &str {
         &self.workflow_dir
     }
+
+    /// The shell used by the workflows that do not set their own.
+    pub fn shell(&self) -> Option<&WorkflowShell> {
+        self.shell.as_ref()
+    }
+
+    /// The patterns of dangerous commands, on top of the built-in ones.
+    pub fn dangerous_patterns(&self) -> &[Regex] {
+        &self.dangerous_patterns
+    }
 }
 
 impl FromStr for Configuration {
@@ -18,11 +35,22 @@ impl FromStr for Configuration {
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         let splitted = input.split('\n').collect::<Vec<&str>>();
         let mut configurations = HashMap::new();
+        let mut dangerous_patterns = Vec::new();
         splitted.iter().for_each(|line| {
             if line.starts_with('#') {
                 return;
             }
 
+            // A pattern is a regular expression, so it is the only value that may contain `=`
+            if let Some((DANGEROUS_PATTERN, pattern)) = line
+                .trim()
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+            {
+                dangerous_patterns.push(pattern);
+                return;
+            }
+
             let configuration = line.trim().split('=').collect::<Vec<&str>>();
             // remove spaces
             let configuration = configuration
@@ -48,7 +76,41 @@ impl FromStr for Configuration {
                 )))
             })?;
 
-        Ok(Self { workflow_dir })
+        let shell = configurations
+            .get("shell")
+            .map(|value| {
+                WorkflowShell::from_str(value).map_err(|_| {
+                    Error::InvalidConfiguration(Some(
+                        format!(
+                            "Failed to read shell from configuration file: unknown shell `{}`",
+                            value
+                        )
+                        .into(),
+                    ))
+                })
+            })
+            .transpose()?;
+
+        let dangerous_patterns = dangerous_patterns
+            .into_iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    Error::InvalidConfiguration(Some(
+                        format!(
+                            "Failed to read {} `{}` from configuration file: {}",
+                            DANGEROUS_PATTERN, pattern, e
+                        )
+                        .into(),
+                    ))
+                })
+            })
+            .collect::<Result<Vec<Regex>, Error>>()?;
+
+        Ok(Self {
+            workflow_dir,
+            shell,
+            dangerous_patterns,
+        })
     }
 }
 
@@ -76,6 +138,56 @@ mod tests {
         assert!(configuration.is_err());
     }
 
+    #[test]
+    fn test_from_configuration_with_shell() {
+        let configuration = r#"
+            workflow_dir = /home/username/workflows
+            shell = zsh
+        "#;
+
+        let configuration = Configuration::from_str(configuration).unwrap();
+        assert_eq!(configuration.shell(), Some(&WorkflowShell::Zsh));
+    }
+
+    #[test]
+    fn test_from_configuration_with_unknown_shell() {
+        let configuration = r#"
+            workflow_dir = /home/username/workflows
+            shell = csh
+        "#;
+
+        let configuration = Configuration::from_str(configuration);
+        assert!(configuration.is_err());
+    }
+
+    #[test]
+    fn test_from_configuration_with_dangerous_patterns() {
+        let configuration = r#"
+            workflow_dir = /home/username/workflows
+            dangerous_pattern = \bdeploy\s+production\b
+            dangerous_pattern = of=/dev/\w+
+        "#;
+
+        let configuration = Configuration::from_str(configuration).unwrap();
+        let patterns = configuration
+            .dangerous_patterns()
+            .iter()
+            .map(Regex::as_str)
+            .collect::<Vec<&str>>();
+        assert_eq!(patterns, vec![r"\bdeploy\s+production\b", r"of=/dev/\w+"]);
+    }
+
+    #[test]
+    fn test_from_configuration_with_invalid_dangerous_pattern() {
+        let configuration = r#"
+            workflow_dir = /home/username/workflows
+            dangerous_pattern = rm (-rf
+        "#;
+
+        let configuration = Configuration::from_str(configuration);
+        assert!(configuration.is_err());
+    }
+
     #[test]
     fn test_from_configuration_with_spaces() {
         let configuration = r#"
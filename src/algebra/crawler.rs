@@ -3,8 +3,11 @@ use std::{
     path::Path,
 };
 
+use crossterm::style::{Color, ResetColor, SetForegroundColor};
+
 use crate::prelude::{
-    Error, File, FileExtension, FileMetadata, Store, Unit, WorkStore, Workflow, WorkflowId,
+    Error, File, FileExtension, FileMetadata, Process, Store, Unit, WorkStore, Workflow,
+    WorkflowId, SHELL,
 };
 
 pub struct Crawler {}
@@ -33,6 +36,19 @@ impl Crawler {
             .collect::<Vec<&str>>();
         let workflows: Vec<Workflow> = prepare_workflows(names, directory)?;
 
+        // A missing interpreter only matters when the workflow is run, so the rest are still indexed
+        missing_interpreters(&workflows)
+            .into_iter()
+            .for_each(|(workflow, program)| {
+                eprintln!(
+                    "{}Warning: the shell `{}` of workflow `{}` was not found in PATH{}",
+                    SetForegroundColor(Color::Yellow),
+                    program,
+                    workflow.name().inner(),
+                    ResetColor
+                );
+            });
+
         let workflows_checksums: HashMap<WorkflowId, u64> = workflows
             .iter()
             .map(|workflow| (workflow.id(), workflow.checksum()))
@@ -45,6 +61,7 @@ impl Crawler {
             .map(|workflow| (workflow.id(), workflow.checksum()))
             .collect::<HashMap<WorkflowId, u64>>();
 
+        #[allow(clippy::unnecessary_map_or)]
         let has_changes = workflows_checksums.iter().any(|(id, checksum)| {
             stored_workflows_checksums
                 .get(id)
@@ -61,6 +78,7 @@ impl Crawler {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
     use crate::prelude::{Store, STORE};
@@ -104,6 +122,17 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_missing_interpreters() {
+        let workflows = vec![
+            parse_workflow_string("{name: sh, command: test, shell: sh}".to_owned()).unwrap(),
+            parse_workflow_string("{name: exec, command: test, shell: exec}".to_owned()).unwrap(),
+        ];
+
+        assert!(missing_interpreters(&workflows).is_empty());
+    }
+
     #[test]
     fn test_crawl() {
         set_env_var();
@@ -125,10 +154,30 @@ fn prepare_workflows(names: &[&str], location: &Path) -> Result<Vec<Workflow>, E
 
     values
         .iter()
-        .map(|value| load_workflow_file(location, Path::new(value)).and_then(parse_workflow_string))
+        .map(|value| {
+            let path = location.join(value);
+            load_workflow_file(location, Path::new(value))
+                .and_then(parse_workflow_string)
+                .map(|workflow| workflow.with_path(&path))
+        })
         .collect::<Result<Vec<Workflow>, Error>>()
 }
 
+/// Find the workflows whose shell is not installed, along with the program that is missing.
+fn missing_interpreters(workflows: &[Workflow]) -> Vec<(&Workflow, &str)> {
+    workflows
+        .iter()
+        .filter_map(|workflow| {
+            workflow
+                .shell()
+                .unwrap_or(&SHELL)
+                .program()
+                .filter(|program| Process::find(program).is_none())
+                .map(|program| (workflow, program))
+        })
+        .collect()
+}
+
 /// Load the workflow file from the given location.
 fn load_workflow_file(workdir: &Path, value: &Path) -> Result<String, Error> {
     let path = Path::new(&workdir).join(value);
@@ -137,5 +186,7 @@ fn load_workflow_file(workdir: &Path, value: &Path) -> Result<String, Error> {
 
 /// Parse the workflow string into a workflow.
 fn parse_workflow_string(workflow: String) -> Result<Workflow, Error> {
-    serde_yaml::from_str::<Workflow>(&workflow).map_err(|e| Error::ParseError(Some(e.into())))
+    serde_yaml::from_str::<Workflow>(&workflow)
+        .map_err(|e| Error::ParseError(Some(e.into())))
+        .and_then(|workflow| workflow.validate().map(|_| workflow))
 }
@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+/// The patterns of commands that are hard or impossible to undo, detected out of the box.
+const BUILT_IN_PATTERNS: &[&str] = &[
+    // rm -rf, rm -r, rm --recursive, rm -v -fr...
+    r"\brm\s+(?:[^\s;&|]+\s+)*?(?:-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\b",
+    // git push --force, git push -f, git push origin +main...
+    r"\bgit\s+push\b[^;&|\n]*?\s(?:--force\S*|-[a-zA-Z]*f[a-zA-Z]*\b|\+\S+)",
+    r"\bgit\s+reset\s+(?:[^\s;&|]+\s+)*?--hard\b",
+    r"\bgit\s+clean\s+(?:[^\s;&|]+\s+)*?-[a-zA-Z]*f",
+    r"(?i)\bdrop\s+(?:table|database|schema)\b",
+    r"(?i)\btruncate\s+table\b",
+    r"\bmkfs(?:\.\w+)?\b",
+    r"\bdd\b[^;&|\n]*\bof=/dev/\S+",
+    r">\s*/dev/(?:sd|hd|nvme|disk)\w*",
+    r"\bkubectl\s+delete\b",
+    r"\bterraform\s+destroy\b",
+];
+
+/// Detects the parts of a command that look dangerous, from the built-in patterns and the
+/// ones added with `dangerous_pattern` in the configuration file.
+pub struct DangerDetector {
+    patterns: Vec<Regex>,
+}
+
+impl DangerDetector {
+    /// The built-in patterns along with the given ones.
+    pub fn new(patterns: &[Regex]) -> Self {
+        Self {
+            patterns: BUILT_IN_PATTERNS
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("Invalid built-in dangerous pattern"))
+                .chain(patterns.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// The parts of the command matching any of the patterns, in order and without overlaps.
+    pub fn find(&self, command: &str) -> Vec<Range<usize>> {
+        let mut parts = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(command).map(|found| found.range()))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<Range<usize>>>();
+        parts.sort_by_key(|part| part.start);
+
+        parts.into_iter().fold(Vec::new(), |mut merged, part| {
+            match merged.last_mut() {
+                Some(last) if part.start <= last.end => last.end = last.end.max(part.end),
+                _ => merged.push(part),
+            }
+            merged
+        })
+    }
+
+    pub fn is_dangerous(&self, command: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.is_match(command))
+    }
+}
+
+impl Default for DangerDetector {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragments(command: &str) -> Vec<&str> {
+        DangerDetector::default()
+            .find(command)
+            .into_iter()
+            .map(|part| &command[part])
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_built_in_patterns() {
+        assert_eq!(fragments("rm -rf ./build"), vec!["rm -rf"]);
+        assert_eq!(fragments("rm -v -fr ./build"), vec!["rm -v -fr"]);
+        assert_eq!(
+            fragments("git push --force origin main"),
+            vec!["git push --force"]
+        );
+        assert_eq!(
+            fragments("git push origin +main"),
+            vec!["git push origin +main"]
+        );
+        assert_eq!(
+            fragments("git reset --hard HEAD~1"),
+            vec!["git reset --hard"]
+        );
+        assert_eq!(fragments("psql -c 'drop table users'"), vec!["drop table"]);
+        assert_eq!(
+            fragments("dd if=image.iso of=/dev/sdb bs=4M"),
+            vec!["dd if=image.iso of=/dev/sdb"]
+        );
+    }
+
+    #[test]
+    fn test_ignore_safe_commands() {
+        for command in [
+            "rm ./build/output.txt",
+            "ls -R; rm notes.txt",
+            "git push origin main --follow-tags",
+            "git reset HEAD~1",
+            "cargo clean",
+            "echo 'dropped tables'",
+        ] {
+            assert!(
+                !DangerDetector::default().is_dangerous(command),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_configured_patterns() {
+        let detector = DangerDetector::new(&[Regex::new(r"\bdeploy\s+production\b").unwrap()]);
+
+        assert!(detector.is_dangerous("./deploy production"));
+        assert!(!detector.is_dangerous("./deploy staging"));
+    }
+
+    #[test]
+    fn test_merge_overlapping_parts() {
+        let detector = DangerDetector::new(&[Regex::new(r"-rf\s+/").unwrap()]);
+        let command = "sudo rm -rf / && rm -r tmp";
+
+        let parts = detector
+            .find(command)
+            .into_iter()
+            .map(|part| &command[part])
+            .collect::<Vec<&str>>();
+
+        assert_eq!(parts, vec!["rm -rf /", "rm -r"]);
+    }
+}
@@ -1,15 +1,33 @@
+pub mod condition;
 pub mod configuration;
 pub mod crawler;
+pub mod detector;
 pub mod file;
 pub mod hasher;
+pub mod process;
+pub mod scheduler;
+pub mod template;
+pub mod watcher;
 
 pub mod prelude {
+    #[doc(inline)]
+    pub use super::condition::*;
     #[doc(inline)]
     pub use super::configuration::*;
     #[doc(inline)]
     pub use super::crawler::*;
     #[doc(inline)]
+    pub use super::detector::*;
+    #[doc(inline)]
     pub use super::file::*;
     #[doc(inline)]
     pub use super::hasher::*;
+    #[doc(inline)]
+    pub use super::process::*;
+    #[doc(inline)]
+    pub use super::scheduler::*;
+    #[doc(inline)]
+    pub use super::template::*;
+    #[doc(inline)]
+    pub use super::watcher::*;
 }
@@ -0,0 +1,1250 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Mutex, Once,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossterm::{cursor, execute, style::ResetColor, terminal};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+
+use crate::prelude::{Error, ExecutionMode, ExitStatus, WorkflowShell};
+
+/// A command to be spawned by the executor.
+pub struct Process {
+    program: String,
+    args: Vec<String>,
+    prefix: Option<String>,
+    env: Vec<(String, String)>,
+    clean_env: bool,
+    cwd: Option<PathBuf>,
+    timeout: Option<Duration>,
+    cleanup: bool,
+}
+
+/// How often a process with a timeout is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long a process that timed out has to stop after `SIGTERM`, before it gets `SIGKILL`.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+/// The signals forwarded to the running processes instead of stopping `workflow` right away.
+#[cfg(unix)]
+const FORWARDED_SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+/// The process groups of the running processes, along with the signal forwarded to each of them.
+static GROUPS: Mutex<Vec<(u32, Option<i32>)>> = Mutex::new(Vec::new());
+/// The last signal `workflow` received while running processes, `0` if none.
+static INTERRUPTED: AtomicI32 = AtomicI32::new(0);
+/// The signal the running processes were cancelled with, `0` if they were not.
+static CANCELLED: AtomicI32 = AtomicI32::new(0);
+/// Installs the handlers of `FORWARDED_SIGNALS` once.
+static FORWARDING: Once = Once::new();
+
+/// The outcome of a `Process` together with whatever was captured from its output.
+#[derive(Debug)]
+pub struct Captured {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+impl Captured {
+    pub fn status(&self) -> &ExitStatus {
+        &self.status
+    }
+
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+}
+
+impl Process {
+    pub fn new(program: &str, args: Vec<String>) -> Self {
+        Self {
+            program: program.to_string(),
+            args,
+            prefix: None,
+            env: Vec::new(),
+            clean_env: false,
+            cwd: None,
+            timeout: None,
+            cleanup: false,
+        }
+    }
+
+    /// Kill the process, along with every process it started, once it runs longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set environment variables on top of the ones inherited from this process.
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Do not inherit any environment variable of this process, so the process only gets the
+    /// ones set with `with_env`.
+    pub fn with_clean_env(mut self, clean_env: bool) -> Self {
+        self.clean_env = clean_env;
+        self
+    }
+
+    /// Run the process even once `workflow` was interrupted or the processes were cancelled, to
+    /// clean up after them.
+    pub fn with_cleanup(mut self, cleanup: bool) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// Run the process in the given directory instead of the current one.
+    pub fn with_cwd(mut self, cwd: Option<&Path>) -> Self {
+        self.cwd = cwd.map(Path::to_path_buf);
+        self
+    }
+
+    /// Prefix every line of output with `[prefix] `, so the output of processes running at the
+    /// same time can be told apart. Such a process is always piped and does not read stdin.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(format!("[{}] ", prefix));
+        self
+    }
+
+    /// A process running the given command with the given shell. With `exec` the command is
+    /// split into words, the first one being the program, and run without any shell.
+    pub fn shell(shell: &WorkflowShell, command: &str) -> Result<Self, Error> {
+        match shell {
+            WorkflowShell::Sh | WorkflowShell::Bash | WorkflowShell::Zsh | WorkflowShell::Fish => {
+                let program = shell.program().unwrap_or("sh");
+                Ok(Self::new(
+                    program,
+                    vec!["-c".to_string(), command.to_string()],
+                ))
+            }
+            WorkflowShell::Pwsh => Ok(Self::new(
+                "pwsh",
+                vec![
+                    "-NoProfile".to_string(),
+                    "-Command".to_string(),
+                    command.to_string(),
+                ],
+            )),
+            WorkflowShell::Exec => {
+                let words = shell_words::split(command)
+                    .map_err(|e| Error::InvalidCommand(Some(e.into())))?;
+                let (program, args) = words.split_first().ok_or(Error::InvalidCommand(Some(
+                    "The command to execute is empty".into(),
+                )))?;
+
+                Ok(Self::new(program, args.to_vec()))
+            }
+        }
+    }
+
+    /// Look for the given program in the directories of `PATH`.
+    pub fn find(program: &str) -> Option<PathBuf> {
+        let paths = std::env::var_os("PATH")?;
+        let extensions: &[&str] = if cfg!(target_os = "windows") {
+            &["exe", "cmd", "bat"]
+        } else {
+            &[]
+        };
+
+        std::env::split_paths(&paths).find_map(|directory| {
+            let path = directory.join(program);
+            std::iter::once(path.clone())
+                .chain(
+                    extensions
+                        .iter()
+                        .map(|extension| path.with_extension(extension)),
+                )
+                .find(|candidate| candidate.is_file())
+        })
+    }
+
+    /// Run the process to completion, wiring its standard streams according to `mode`.
+    ///
+    /// On unix the process leads a process group of its own, which is handed the terminal while
+    /// it runs. `SIGINT`, `SIGTERM` and `SIGHUP` received by `workflow` are forwarded to it and
+    /// its status is marked as interrupted. Once `workflow` is interrupted, or the processes are
+    /// cancelled, no other process is started, unless it is a cleanup one.
+    ///
+    /// # Arguments
+    /// * `mode` - How stdin, stdout and stderr are handled
+    ///
+    /// # Returns
+    /// * The `Captured` outcome of the process or an `Error` if it could not be spawned
+    pub fn run(&self, mode: ExecutionMode) -> Result<Captured, Error> {
+        if let Some(signal) = interrupted().or(cancelled()).filter(|_| !self.cleanup) {
+            return Ok(Captured {
+                status: ExitStatus::new(None, None).into_interrupted(signal),
+                stdout: String::new(),
+                stderr: String::new(),
+            });
+        }
+
+        match (mode, &self.prefix) {
+            (_, Some(_)) => self.run_pipe(),
+            (ExecutionMode::Inherit, None) => self.run_inherit(),
+            (ExecutionMode::Pipe, None) => self.run_pipe(),
+            (ExecutionMode::Pty, None) => self.run_pty(),
+        }
+    }
+
+    /// Run the process without showing its output nor letting it read stdin, to use what it
+    /// prints as a value.
+    ///
+    /// # Returns
+    /// * The `Captured` output or an `Error` if the process could not be spawned
+    pub fn capture(&self) -> Result<Captured, Error> {
+        let mut command = self.command();
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let (mut child, group) = self.spawn(command)?;
+
+        let stdout = child.stdout.take().map(|out| tee(out, io::sink()));
+        let stderr = child.stderr.take().map(|err| tee(err, io::sink()));
+
+        let status = wait(&mut child, self.timeout).map_err(|e| Error::Io(Some(e.into())))?;
+        let status = group.leave(status);
+
+        Ok(Captured {
+            status,
+            stdout: join(stdout),
+            stderr: join(stderr),
+        })
+    }
+
+    /// Start the process in the background with its output written to the given files, without
+    /// waiting for it. On unix it leads a session of its own, so it outlives the terminal.
+    ///
+    /// # Returns
+    /// * The id of the process or an `Error` if it could not be spawned
+    pub fn spawn_detached(&self, stdout: File, stderr: File) -> Result<u32, Error> {
+        let mut command = self.command();
+        command.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            // SAFETY: `setsid` is async-signal-safe, so it can be called between fork and exec
+            unsafe {
+                command.pre_exec(|| match libc::setsid() {
+                    -1 => Err(io::Error::last_os_error()),
+                    _ => Ok(()),
+                });
+            }
+        }
+
+        command
+            .spawn()
+            .map(|child| child.id())
+            .map_err(|e| Error::Io(Some(e.into())))
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if self.clean_env {
+            command.env_clear();
+        }
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+
+    /// Spawn the command as the leader of a new process group. When it reads from the terminal
+    /// `workflow` is in the foreground of, the group is brought to the foreground, as shells do
+    /// with their jobs, so that reading does not stop it.
+    fn spawn(&self, command: Command) -> Result<(Child, Group), Error> {
+        forward_signals();
+        let foreground = self.prefix.is_none() && is_foreground();
+        let child = spawn_in_group(command, None, foreground)?;
+
+        let group = Group::join(child.id(), foreground, self.cleanup);
+        Ok((child, group))
+    }
+
+    fn run_inherit(&self) -> Result<Captured, Error> {
+        let (mut child, group) = self.spawn(self.command())?;
+        let status = wait(&mut child, self.timeout).map_err(|e| Error::Io(Some(e.into())))?;
+        let status = group.leave(status);
+
+        Ok(Captured {
+            status,
+            stdout: String::new(),
+            stderr: String::new(),
+        })
+    }
+
+    fn run_pipe(&self) -> Result<Captured, Error> {
+        let mut command = self.command();
+        command
+            .stdin(match self.prefix {
+                Some(_) => Stdio::null(),
+                None => Stdio::inherit(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let (mut child, group) = self.spawn(command)?;
+
+        let stdout = child.stdout.take().map(|out| match &self.prefix {
+            Some(prefix) => tee(out, Prefixed::new(io::stdout(), prefix)),
+            None => tee(out, io::stdout()),
+        });
+        let stderr = child.stderr.take().map(|err| match &self.prefix {
+            Some(prefix) => tee(err, Prefixed::new(io::stderr(), prefix)),
+            None => tee(err, io::stderr()),
+        });
+
+        let status = wait(&mut child, self.timeout).map_err(|e| Error::Io(Some(e.into())))?;
+        let status = group.leave(status);
+
+        Ok(Captured {
+            status,
+            stdout: join(stdout),
+            stderr: join(stderr),
+        })
+    }
+
+    fn run_pty(&self) -> Result<Captured, Error> {
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| Error::Io(Some(e.into())))?;
+
+        let mut builder = CommandBuilder::new(&self.program);
+        builder.args(&self.args);
+        if self.clean_env {
+            builder.env_clear();
+        }
+        self.env
+            .iter()
+            .for_each(|(key, value)| builder.env(key, value));
+        // Otherwise the command would start in the home directory
+        if let Some(cwd) = self.cwd.clone().or(std::env::current_dir().ok()) {
+            builder.cwd(cwd);
+        }
+
+        forward_signals();
+        let mut child = pair
+            .slave
+            .spawn_command(builder)
+            .map_err(|e| Error::Io(Some(e.into())))?;
+        // The child leads a session of its own, hence a process group, and has its own terminal
+        let group = Group::join(child.process_id().unwrap_or_default(), false, self.cleanup);
+        // The reader only reaches the end once every handle to the slave is closed
+        drop(pair.slave);
+
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| Error::Io(Some(e.into())))?;
+        let mut writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| Error::Io(Some(e.into())))?;
+
+        let _raw = RawMode::enable()?;
+
+        let output = tee(reader, io::stdout());
+        // Never joined: it stays blocked on stdin until the next key press after the command ends
+        thread::spawn(move || io::copy(&mut io::stdin(), &mut writer));
+
+        let status = wait(&mut child, self.timeout).map_err(|e| Error::Io(Some(e.into())))?;
+        let status = group.leave(status);
+        drop(pair.master);
+
+        Ok(Captured {
+            status,
+            stdout: join(Some(output)),
+            // A pseudo-terminal merges stderr into stdout
+            stderr: String::new(),
+        })
+    }
+}
+
+/// A spawned process that can be waited for and killed.
+trait Wait {
+    fn pid(&self) -> Option<u32>;
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+impl Wait for Child {
+    fn pid(&self) -> Option<u32> {
+        Some(self.id())
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Child::try_wait(self).map(|status| status.map(ExitStatus::from))
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        Child::wait(self).map(ExitStatus::from)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)
+    }
+}
+
+/// The child of a pseudo-terminal. On unix the raw wait status is read, since the one reported
+/// by `portable_pty` only keeps the name of the signal that terminated the child.
+impl Wait for Box<dyn portable_pty::Child + Send + Sync> {
+    fn pid(&self) -> Option<u32> {
+        self.process_id()
+    }
+
+    #[cfg(unix)]
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        waitpid(self.pid(), libc::WNOHANG)
+    }
+
+    #[cfg(not(unix))]
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        portable_pty::Child::try_wait(self.as_mut()).map(|status| {
+            status.map(|status| ExitStatus::new(Some(status.exit_code() as i32), None))
+        })
+    }
+
+    #[cfg(unix)]
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        waitpid(self.pid(), 0).map(|status| status.unwrap_or(ExitStatus::new(None, None)))
+    }
+
+    #[cfg(not(unix))]
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        portable_pty::Child::wait(self.as_mut())
+            .map(|status| ExitStatus::new(Some(status.exit_code() as i32), None))
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        portable_pty::ChildKiller::kill(self.as_mut())
+    }
+}
+
+#[cfg(unix)]
+fn waitpid(pid: Option<u32>, options: libc::c_int) -> io::Result<Option<ExitStatus>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid =
+        pid.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The child has no process id"))?;
+    let mut status = 0;
+    loop {
+        // SAFETY: `pid` is a child of this process that has not been reaped yet
+        match unsafe { libc::waitpid(pid as libc::pid_t, &mut status, options) } {
+            0 => return Ok(None),
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            _ => return Ok(Some(std::process::ExitStatus::from_raw(status).into())),
+        }
+    }
+}
+
+/// Whether the process started by `Process::spawn_detached` with the given id is still running.
+/// On unix the process has to lead its own session, so that an id reused by an unrelated
+/// process after a reboot is not mistaken for it.
+pub fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: `getsid` only reads the session of the process
+        unsafe { libc::getsid(pid as libc::pid_t) == pid as libc::pid_t }
+    }
+    #[cfg(not(unix))]
+    {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    }
+}
+
+/// Wait for the process started by `Process::spawn_detached` with the given id to exit, from
+/// the process that started it.
+///
+/// # Returns
+/// * The `ExitStatus` of the process or an `Error` if it cannot be waited for
+pub fn wait_detached(pid: u32) -> Result<ExitStatus, Error> {
+    #[cfg(unix)]
+    {
+        waitpid(Some(pid), 0)
+            .map(|status| status.unwrap_or(ExitStatus::new(None, None)))
+            .map_err(|e| Error::Io(Some(e.into())))
+    }
+    #[cfg(not(unix))]
+    {
+        while is_running(pid) {
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(ExitStatus::new(None, None))
+    }
+}
+
+/// Stop the process started by `Process::spawn_detached` with the given id, along with every
+/// process it started. It is sent `SIGTERM`, then `SIGKILL` if it is still running after
+/// `KILL_GRACE`.
+///
+/// # Returns
+/// * The signal that stopped the process or an `Error` if it could not be sent
+pub fn terminate(pid: u32) -> Result<i32, Error> {
+    #[cfg(unix)]
+    {
+        let signal = |signal| {
+            // SAFETY: the process leads its own session, hence a process group of the same id
+            match unsafe { libc::kill(-(pid as libc::pid_t), signal) } {
+                -1 => Err(Error::Io(Some(io::Error::last_os_error().into()))),
+                _ => Ok(signal),
+            }
+        };
+
+        signal(libc::SIGTERM)?;
+        let deadline = Instant::now() + KILL_GRACE;
+        while is_running(pid) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        match is_running(pid) {
+            true => signal(libc::SIGKILL),
+            false => Ok(libc::SIGTERM),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .status()
+            .map_err(|e| Error::Io(Some(e.into())))
+            .map(|_| 9)
+    }
+}
+
+/// The signal `workflow` received while running processes, if any.
+pub fn interrupted() -> Option<i32> {
+    match INTERRUPTED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// Handle `FORWARDED_SIGNALS` from now on: instead of stopping `workflow`, each of them is
+/// recorded and sent to the process group of every running process.
+fn forward_signals() {
+    #[cfg(unix)]
+    FORWARDING.call_once(|| {
+        // Without the handlers the signals keep stopping `workflow`, which is not worth failing for
+        let Ok(mut signals) = signal_hook::iterator::Signals::new(FORWARDED_SIGNALS) else {
+            return;
+        };
+
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                INTERRUPTED.store(signal, Ordering::SeqCst);
+                signal_groups(signal);
+            }
+        });
+    });
+}
+
+/// The signal the running processes were cancelled with, if they were and not resumed since.
+pub fn cancelled() -> Option<i32> {
+    match CANCELLED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// Stop every running process, along with every process it started, without stopping
+/// `workflow`, e.g. to run a workflow again from the start. They are sent `SIGTERM`, then
+/// `SIGKILL` if they are still running after `KILL_GRACE`, and no other process is started
+/// until `resume` is called. Their status is marked as interrupted.
+pub fn cancel() {
+    #[cfg(unix)]
+    {
+        CANCELLED.store(libc::SIGTERM, Ordering::SeqCst);
+        signal_groups(libc::SIGTERM);
+
+        let deadline = Instant::now() + KILL_GRACE;
+        let is_running = || !GROUPS.lock().unwrap_or_else(|e| e.into_inner()).is_empty();
+        while is_running() && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if is_running() {
+            signal_groups(libc::SIGKILL);
+        }
+    }
+}
+
+/// Start processes again after `cancel`.
+pub fn resume() {
+    CANCELLED.store(0, Ordering::SeqCst);
+}
+
+/// Send the signal to the process group of every running process, marking it as forwarded.
+#[cfg(unix)]
+fn signal_groups(signal: libc::c_int) {
+    let mut groups = GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+    for (pid, forwarded) in groups.iter_mut() {
+        forwarded.get_or_insert(signal);
+        // SAFETY: the group is led by a child of this process that is not reaped yet
+        unsafe { libc::kill(-(*pid as libc::pid_t), signal) };
+    }
+}
+
+/// Whether `workflow` is in the foreground of the terminal it reads from.
+fn is_foreground() -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: both only read the process groups of this process and of the terminal
+        io::stdin().is_terminal()
+            && unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Bring the given process group to the foreground of the terminal `workflow` reads from.
+#[cfg(unix)]
+fn give_terminal(group: libc::pid_t) {
+    // SAFETY: `SIGTTOU` is ignored for the time of the call only, since a process outside of
+    // the foreground is stopped when changing it otherwise
+    unsafe {
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, group);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+}
+
+/// Run the processes as a pipeline, as shells do with `a | b | c`: the stdout of each process is
+/// the stdin of the next one, while the first one reads the stdin of `workflow` and the last one
+/// writes to its stdout. They all share the process group of the first one, so a signal reaches
+/// every one of them. Their timeout and prefix are ignored.
+///
+/// # Returns
+/// * The status of every process, in order, or an `Error` if one could not be spawned
+pub fn run_pipeline(processes: &[Process]) -> Result<Vec<ExitStatus>, Error> {
+    forward_signals();
+    let foreground = is_foreground();
+    let mut children: Vec<Child> = Vec::new();
+    let mut group: Option<Group> = None;
+    let mut input: Option<ChildStdout> = None;
+
+    for (index, process) in processes.iter().enumerate() {
+        let mut command = process.command();
+        command.stdin(input.take().map_or(Stdio::inherit(), Stdio::from));
+        if index + 1 < processes.len() {
+            command.stdout(Stdio::piped());
+        }
+
+        match spawn_in_group(command, group.as_ref().map(|group| group.pid), foreground) {
+            Ok(mut child) => {
+                input = child.stdout.take();
+                group.get_or_insert_with(|| Group::join(child.id(), foreground, false));
+                children.push(child);
+            }
+            Err(error) => {
+                // The stages already running would wait forever for the missing one
+                for child in &mut children {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                if let Some(group) = group {
+                    group.leave(ExitStatus::new(None, None));
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    let mut statuses = children
+        .iter_mut()
+        .map(|child| wait(child, None))
+        .collect::<io::Result<Vec<ExitStatus>>>()
+        .map_err(|e| Error::Io(Some(e.into())))?;
+    // Whichever stage a signal stopped tells whether the pipeline was interrupted
+    let stopped = statuses
+        .iter()
+        .find(|status| status.signal().is_some())
+        .or(statuses.first())
+        .cloned();
+    if let (Some(group), Some(stopped)) = (group, stopped) {
+        if let Some(signal) = group.leave(stopped).interrupted() {
+            statuses = statuses
+                .into_iter()
+                .map(|status| match status.success() {
+                    true => status,
+                    false => status.into_interrupted(signal),
+                })
+                .collect();
+        }
+    }
+
+    Ok(statuses)
+}
+
+/// Spawn the command in the process group led by `leader`, or as the leader of a new one. When
+/// it reads from the terminal `workflow` is in the foreground of, the group is brought to the
+/// foreground, as shells do with their jobs, so that reading does not stop it.
+fn spawn_in_group(
+    mut command: Command,
+    leader: Option<u32>,
+    foreground: bool,
+) -> Result<Child, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        let leader = leader.unwrap_or(0) as libc::pid_t;
+        // SAFETY: `setpgid`, `signal`, `tcsetpgrp` and `getpid` are async-signal-safe, so
+        // they can be called between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setpgid(0, leader) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Also done by the parent, whichever of them runs first
+                if foreground {
+                    give_terminal(match leader {
+                        0 => libc::getpid(),
+                        leader => leader,
+                    });
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let child = command.spawn().map_err(|e| {
+        // The child may have taken the terminal before failing to start the program
+        #[cfg(unix)]
+        if foreground && leader.is_none() {
+            // SAFETY: `getpgrp` only reads the process group of this process
+            give_terminal(unsafe { libc::getpgrp() });
+        }
+        Error::Io(Some(e.into()))
+    })?;
+    #[cfg(unix)]
+    if foreground {
+        give_terminal(leader.unwrap_or(child.id()) as libc::pid_t);
+    }
+
+    Ok(child)
+}
+
+/// The process group of a running process, which gets the signals `workflow` receives until
+/// it leaves.
+struct Group {
+    pid: u32,
+    foreground: bool,
+}
+
+impl Group {
+    /// Forward signals to the group from now on. A cleanup process is only sent the signals
+    /// received from now on, not the one that stopped the other processes.
+    fn join(pid: u32, foreground: bool, cleanup: bool) -> Self {
+        let signal = interrupted().or(cancelled()).filter(|_| !cleanup);
+        let mut groups = GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+        groups.push((pid, signal));
+        // The signal arrived while the process was being spawned
+        #[cfg(unix)]
+        if let Some(signal) = signal {
+            // SAFETY: the group is led by a child of this process that is not reaped yet
+            unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+        }
+
+        Self { pid, foreground }
+    }
+
+    /// Stop forwarding signals to the group, once its leader exited with the given status, and
+    /// mark the status as interrupted if a signal was forwarded to it. As with shells, a process
+    /// in the foreground that is stopped by `SIGINT` interrupts `workflow` too, since the
+    /// signal only reached the process.
+    fn leave(self, status: ExitStatus) -> ExitStatus {
+        let forwarded = {
+            let mut groups = GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+            let index = groups.iter().position(|(pid, _)| *pid == self.pid);
+            index.and_then(|index| groups.remove(index).1)
+        };
+
+        #[cfg(unix)]
+        let forwarded = forwarded.or_else(|| {
+            let signal = status
+                .signal()
+                .filter(|&signal| self.foreground && signal == libc::SIGINT)?;
+            INTERRUPTED.store(signal, Ordering::SeqCst);
+            Some(signal)
+        });
+
+        match forwarded {
+            Some(signal) => status.into_interrupted(signal),
+            None => status,
+        }
+    }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if self.foreground {
+            // SAFETY: `getpgrp` only reads the process group of this process
+            give_terminal(unsafe { libc::getpgrp() });
+        }
+    }
+}
+
+/// The state of the terminal before a command runs, put back once dropped, however the
+/// command ended: its settings (e.g. echo or raw mode), its size, the colour and the cursor.
+pub struct TerminalState {
+    #[cfg(unix)]
+    termios: Option<libc::termios>,
+    size: Option<(u16, u16)>,
+}
+
+impl TerminalState {
+    /// Save the state of the terminal `workflow` reads from, if any.
+    pub fn save() -> Self {
+        let is_terminal = io::stdin().is_terminal();
+
+        #[cfg(unix)]
+        let termios = is_terminal.then(|| {
+            let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+            // SAFETY: `termios` is only read once `tcgetattr` filled it
+            unsafe {
+                (libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) == 0)
+                    .then(|| termios.assume_init())
+            }
+        });
+
+        Self {
+            #[cfg(unix)]
+            termios: termios.flatten(),
+            // Without a terminal (e.g. in CI) there is no size to restore
+            size: terminal::size().ok().filter(|_| is_terminal),
+        }
+    }
+}
+
+impl Drop for TerminalState {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(termios) = &self.termios {
+            // SAFETY: `termios` was filled by `tcgetattr` for the same terminal
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, termios) };
+        }
+
+        if io::stdout().is_terminal() {
+            let _ = execute!(io::stdout(), ResetColor, cursor::Show);
+        }
+        if let Some((cols, rows)) = self.size {
+            let _ = execute!(io::stdout(), terminal::SetSize(cols, rows));
+        }
+    }
+}
+
+/// Wait for the child to exit. Once it runs longer than `timeout` its process group is sent
+/// `SIGTERM`, then `SIGKILL` after `KILL_GRACE`, and its status is marked as timed out.
+fn wait<W: Wait>(child: &mut W, timeout: Option<Duration>) -> io::Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return child.wait();
+    };
+
+    if let Some(status) = wait_until(child, Instant::now() + timeout)? {
+        return Ok(status);
+    }
+
+    #[cfg(unix)]
+    if let Some(pid) = child.pid() {
+        let signal = |signal| {
+            // SAFETY: the group is led by a child of this process that has not been reaped yet,
+            // so it cannot be another process group
+            unsafe { libc::kill(-(pid as libc::pid_t), signal) }
+        };
+
+        signal(libc::SIGTERM);
+        let status = match wait_until(child, Instant::now() + KILL_GRACE)? {
+            Some(status) => status,
+            None => {
+                signal(libc::SIGKILL);
+                child.wait()?
+            }
+        };
+        // What the leader started may have outlived it while keeping its output open
+        signal(libc::SIGKILL);
+
+        return Ok(status.into_timed_out());
+    }
+
+    child.kill()?;
+    child.wait().map(ExitStatus::into_timed_out)
+}
+
+/// Poll the child until it exits or the deadline is reached.
+fn wait_until<W: Wait>(child: &mut W, deadline: Instant) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+}
+
+/// Keeps the terminal in raw mode, so keys reach the pseudo-terminal untouched, until dropped.
+struct RawMode {
+    enabled: bool,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self, Error> {
+        let enabled = io::stdin().is_terminal();
+        if enabled {
+            terminal::enable_raw_mode().map_err(|e| Error::Io(Some(e.into())))?;
+        }
+        Ok(Self { enabled })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if self.enabled {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Copy everything from `reader` to `writer` as it arrives, keeping a copy of it.
+///
+/// A read error (e.g. `EIO` once the child of a pseudo-terminal is gone) ends the stream.
+fn tee<R, W>(mut reader: R, mut writer: W) -> JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut captured = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => {
+                    let chunk = &buffer[..read];
+                    let _ = writer.write_all(chunk).and_then(|_| writer.flush());
+                    captured.extend_from_slice(chunk);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        captured
+    })
+}
+
+/// A writer putting a prefix in front of every line, writing whole lines only so that lines
+/// of other writers sharing the same stream are not cut in the middle.
+struct Prefixed<W: Write> {
+    writer: W,
+    prefix: String,
+    line: Vec<u8>,
+}
+
+impl<W: Write> Prefixed<W> {
+    fn new(writer: W, prefix: &str) -> Self {
+        Self {
+            writer,
+            prefix: prefix.to_string(),
+            line: Vec::new(),
+        }
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let mut line = self.prefix.clone().into_bytes();
+        line.append(&mut self.line);
+        if line.last() != Some(&b'\n') {
+            line.push(b'\n');
+        }
+        self.writer.write_all(&line)
+    }
+}
+
+impl<W: Write> Write for Prefixed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.line.push(*byte);
+            if *byte == b'\n' {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for Prefixed<W> {
+    fn drop(&mut self) {
+        // The last line of the output may not end with a newline
+        if !self.line.is_empty() {
+            let _ = self.write_line().and_then(|_| self.writer.flush());
+        }
+    }
+}
+
+fn join(handle: Option<JoinHandle<Vec<u8>>>) -> String {
+    handle
+        .and_then(|handle| handle.join().ok())
+        .map(|captured| String::from_utf8_lossy(&captured).into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_pipe_captures_streams() {
+        let process = Process::shell(&WorkflowShell::Sh, "echo hello; echo oops 1>&2").unwrap();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert!(captured.success());
+        assert_eq!(captured.stdout(), "hello\n");
+        assert_eq!(captured.stderr(), "oops\n");
+    }
+
+    #[test]
+    fn test_run_pipe_with_failing_command() {
+        let process = Process::shell(&WorkflowShell::Sh, "exit 3").unwrap();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert!(!captured.success());
+        assert_eq!(captured.status().code(), Some(3));
+    }
+
+    #[test]
+    fn test_run_pipe_with_signaled_command() {
+        let process = Process::shell(&WorkflowShell::Sh, "kill -TERM $$").unwrap();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert_eq!(captured.status().signal(), Some(15));
+        assert_eq!(captured.status().exit_code(), 143);
+    }
+
+    #[test]
+    fn test_run_in_own_process_group() {
+        let process =
+            Process::shell(&WorkflowShell::Sh, "ps -o pgid= -p $$ | tr -d ' '; echo $$").unwrap();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+        let lines = captured.stdout().lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], lines[1]);
+        assert_eq!(captured.status().interrupted(), None);
+    }
+
+    #[test]
+    fn test_run_inherit_captures_nothing() {
+        let process = Process::shell(&WorkflowShell::Sh, "true").unwrap();
+        let captured = process.run(ExecutionMode::Inherit).unwrap();
+
+        assert!(captured.success());
+        assert!(captured.stdout().is_empty());
+        assert!(captured.stderr().is_empty());
+    }
+
+    #[test]
+    fn test_run_exec_without_shell() {
+        let process = Process::shell(&WorkflowShell::Exec, "echo 'a   b' $HOME").unwrap();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert_eq!(captured.stdout(), "a   b $HOME\n");
+    }
+
+    #[test]
+    fn test_exec_with_empty_command() {
+        let process = Process::shell(&WorkflowShell::Exec, "  ");
+
+        assert!(matches!(process, Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_prefixed_writer() {
+        let mut output = Vec::new();
+        {
+            let mut writer = Prefixed::new(&mut output, "[build] ");
+            writer.write_all(b"one\ntw").unwrap();
+            writer.write_all(b"o\nthree").unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[build] one\n[build] two\n[build] three\n"
+        );
+    }
+
+    #[test]
+    fn test_run_prefixed_captures_raw_output() {
+        let process = Process::shell(&WorkflowShell::Sh, "echo hello; read line")
+            .unwrap()
+            .with_prefix("greet");
+        let captured = process.run(ExecutionMode::Pty).unwrap();
+
+        // Never reading stdin, the `read` sees the end of it right away
+        assert!(!captured.success());
+        assert_eq!(captured.stdout(), "hello\n");
+    }
+
+    #[test]
+    fn test_run_with_env_and_cwd() {
+        let process = Process::shell(&WorkflowShell::Sh, "echo $GREETING; pwd")
+            .unwrap()
+            .with_env(vec![("GREETING".to_string(), "hello".to_string())])
+            .with_cwd(Some(Path::new("/")));
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert_eq!(captured.stdout(), "hello\n/\n");
+    }
+
+    #[test]
+    fn test_capture() {
+        let process = Process::shell(&WorkflowShell::Sh, "echo out; echo err >&2; exit 2").unwrap();
+        let captured = process.capture().unwrap();
+
+        assert_eq!(captured.status().code(), Some(2));
+        assert_eq!(captured.stdout(), "out\n");
+        assert_eq!(captured.stderr(), "err\n");
+    }
+
+    #[test]
+    fn test_run_pipeline() {
+        let output = std::env::temp_dir().join("workflow_test_run_pipeline.txt");
+        let processes = [
+            Process::shell(&WorkflowShell::Sh, "printf 'b\\na\\n'; exit 3").unwrap(),
+            Process::shell(&WorkflowShell::Sh, "sort").unwrap(),
+            Process::shell(&WorkflowShell::Sh, "cat > \"$OUTPUT\"; exit 1")
+                .unwrap()
+                .with_env(vec![(
+                    "OUTPUT".to_string(),
+                    output.to_string_lossy().to_string(),
+                )]),
+        ];
+        let statuses = run_pipeline(&processes).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap_or_default();
+
+        assert_eq!(written, "a\nb\n");
+        assert_eq!(
+            statuses,
+            vec![
+                ExitStatus::new(Some(3), None),
+                ExitStatus::new(Some(0), None),
+                ExitStatus::new(Some(1), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_with_clean_env() {
+        std::env::set_var("WORKFLOW_TEST_SECRET", "secret");
+        let process = Process::shell(
+            &WorkflowShell::Sh,
+            "echo \"$WORKFLOW_TEST_SECRET$GREETING\"",
+        )
+        .unwrap()
+        .with_env(vec![("GREETING".to_string(), "hello".to_string())])
+        .with_clean_env(true);
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        assert_eq!(captured.stdout(), "hello\n");
+    }
+
+    #[test]
+    fn test_run_with_timeout_kills_process_group() {
+        let process = Process::shell(&WorkflowShell::Sh, "sleep 5 & sleep 5; echo done")
+            .unwrap()
+            .with_timeout(Some(Duration::from_millis(200)));
+        let start = Instant::now();
+        let captured = process.run(ExecutionMode::Pipe).unwrap();
+
+        // The output is only complete once the background `sleep` is killed too
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(captured.status().is_timed_out());
+        assert_eq!(captured.status().exit_code(), 124);
+        assert!(captured.stdout().is_empty());
+    }
+
+    #[test]
+    fn test_run_within_timeout() {
+        let process = Process::shell(&WorkflowShell::Sh, "echo fast")
+            .unwrap()
+            .with_timeout(Some(Duration::from_secs(5)));
+        let captured = process.run(ExecutionMode::Inherit).unwrap();
+
+        assert!(captured.success());
+        assert!(!captured.status().is_timed_out());
+    }
+
+    #[test]
+    fn test_spawn_detached_writes_output_to_files() {
+        let directory = std::env::temp_dir().join("workflow_test_spawn_detached");
+        std::fs::create_dir_all(&directory).unwrap();
+        let stdout = File::create(directory.join("stdout.log")).unwrap();
+        let stderr = File::create(directory.join("stderr.log")).unwrap();
+
+        let pid = Process::shell(&WorkflowShell::Sh, "echo out; echo err 1>&2; sleep 0.2")
+            .unwrap()
+            .spawn_detached(stdout, stderr)
+            .unwrap();
+        assert!(is_running(pid));
+
+        // A detached process is reaped by init, except here where the tests are its parent
+        let status = waitpid(Some(pid), 0).unwrap().unwrap();
+        let stdout = std::fs::read_to_string(directory.join("stdout.log")).unwrap();
+        let stderr = std::fs::read_to_string(directory.join("stderr.log")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap_or_default();
+
+        assert!(status.success());
+        assert_eq!(stdout, "out\n");
+        assert_eq!(stderr, "err\n");
+    }
+
+    #[test]
+    fn test_find_program() {
+        assert!(Process::find("sh").is_some());
+        assert!(Process::find("surely-not-an-installed-shell").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{num::NonZeroUsize, sync::mpsc, thread};
+
+use crate::prelude::{Captured, Error, Step, Unit};
+
+/// Where a step stands while the steps of a workflow are scheduled.
+#[derive(Debug)]
+pub enum StepState {
+    Pending,
+    Running,
+    Finished(Captured),
+    Skipped,
+}
+
+impl StepState {
+    /// Whether the steps needing this one can start.
+    fn is_satisfied(&self, step: &Step) -> bool {
+        matches!(self, StepState::Finished(captured) if captured.success() || step.continue_on_error())
+    }
+
+    /// Whether the steps needing this one will never start.
+    fn is_blocking(&self, step: &Step) -> bool {
+        match self {
+            StepState::Finished(captured) => !captured.success() && !step.continue_on_error(),
+            StepState::Skipped => true,
+            StepState::Pending | StepState::Running => false,
+        }
+    }
+}
+
+/// Runs the steps of a workflow as soon as the steps they need are done, with at most
+/// `concurrency` of them at the same time.
+pub struct Scheduler<'a> {
+    steps: &'a [Step],
+    concurrency: NonZeroUsize,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(steps: &'a [Step], concurrency: NonZeroUsize) -> Self {
+        Self { steps, concurrency }
+    }
+
+    /// Run every step, starting the ready ones in the order they are given. Once a step fails
+    /// without `continue_on_error` or is interrupted, the running steps are awaited and no
+    /// other step starts.
+    ///
+    /// # Arguments
+    /// * `run` - Runs the step at the given index, on its own thread
+    /// * `report` - Called every time a step starts, finishes or is skipped
+    ///
+    /// # Returns
+    /// * The final state of every step or an `Error` if a step could not be run at all
+    pub fn run<R, E>(&self, run: R, mut report: E) -> Result<Vec<StepState>, Error>
+    where
+        R: Fn(usize) -> Result<Captured, String> + Sync,
+        E: FnMut(usize, &StepState) -> Result<Unit, Error>,
+    {
+        let mut states = self
+            .steps
+            .iter()
+            .map(|_| StepState::Pending)
+            .collect::<Vec<StepState>>();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| -> Result<Unit, Error> {
+            let mut running = 0;
+            let mut stopped = false;
+            let mut failure = None;
+
+            loop {
+                for (index, step) in self.steps.iter().enumerate() {
+                    if !matches!(states[index], StepState::Pending) {
+                        continue;
+                    }
+
+                    let needs = self.needs(step);
+                    if stopped
+                        || needs
+                            .iter()
+                            .any(|&need| states[need].is_blocking(&self.steps[need]))
+                    {
+                        states[index] = StepState::Skipped;
+                        report(index, &states[index])?;
+                    } else if running < self.concurrency.get()
+                        && needs
+                            .iter()
+                            .all(|&need| states[need].is_satisfied(&self.steps[need]))
+                    {
+                        states[index] = StepState::Running;
+                        report(index, &states[index])?;
+                        running += 1;
+
+                        let sender = sender.clone();
+                        let run = &run;
+                        scope.spawn(move || sender.send((index, run(index))));
+                    }
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (index, result) = receiver.recv().map_err(|e| Error::Io(Some(e.into())))?;
+                running -= 1;
+
+                match result {
+                    Ok(captured) => {
+                        // An interrupted step stops the run, even if it may fail
+                        stopped |= captured.status().interrupted().is_some()
+                            || !captured.success() && !self.steps[index].continue_on_error();
+                        states[index] = StepState::Finished(captured);
+                        report(index, &states[index])?;
+                    }
+                    Err(error) => {
+                        stopped = true;
+                        states[index] = StepState::Skipped;
+                        failure.get_or_insert(error);
+                    }
+                }
+            }
+
+            // Steps needing a step skipped late in the last pass are still pending
+            for (index, state) in states.iter_mut().enumerate() {
+                if matches!(state, StepState::Pending) {
+                    *state = StepState::Skipped;
+                    report(index, state)?;
+                }
+            }
+
+            match failure {
+                Some(error) => Err(Error::Io(Some(error.into()))),
+                None => Ok(()),
+            }
+        })?;
+
+        Ok(states)
+    }
+
+    fn needs(&self, step: &Step) -> Vec<usize> {
+        step.needs()
+            .iter()
+            .filter_map(|need| self.steps.iter().position(|other| other.name() == need))
+            .collect()
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::prelude::{ExecutionMode, Process, WorkflowShell};
+
+    fn run_steps(steps: &[Step], concurrency: usize) -> (Vec<StepState>, Vec<String>) {
+        let events = Mutex::new(Vec::new());
+        let states = Scheduler::new(steps, NonZeroUsize::new(concurrency).unwrap())
+            .run(
+                |index| {
+                    Process::shell(&WorkflowShell::Sh, steps[index].command().inner())
+                        .and_then(|process| process.run(ExecutionMode::Pipe))
+                        .map_err(|e| format!("{:?}", e))
+                },
+                |index, state| {
+                    let event = match state {
+                        StepState::Running => "running",
+                        StepState::Finished(_) => "finished",
+                        StepState::Skipped => "skipped",
+                        StepState::Pending => "pending",
+                    };
+                    events.lock().unwrap().push(format!(
+                        "{} {}",
+                        steps[index].name().inner(),
+                        event
+                    ));
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        (states, events.into_inner().unwrap())
+    }
+
+    #[test]
+    fn test_run_steps_in_order_of_needs() {
+        let steps = vec![
+            Step::new("a", "true", Vec::new(), false),
+            Step::new("b", "true", Vec::new(), false).with_needs(vec!["a"]),
+        ];
+
+        let (states, events) = run_steps(&steps, 4);
+
+        assert!(states
+            .iter()
+            .all(|state| matches!(state, StepState::Finished(_))));
+        assert_eq!(
+            events,
+            vec!["a running", "a finished", "b running", "b finished"]
+        );
+    }
+
+    #[test]
+    fn test_run_independent_steps_in_parallel() {
+        let steps = vec![
+            Step::new("a", "sleep 0.5", Vec::new(), false),
+            Step::new("b", "sleep 0.5", Vec::new(), false),
+            Step::new("c", "sleep 0.5", Vec::new(), false),
+        ];
+
+        let start = Instant::now();
+        run_steps(&steps, 3);
+
+        assert!(start.elapsed() < Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn test_skip_steps_after_failure() {
+        let steps = vec![
+            Step::new("a", "exit 1", Vec::new(), false),
+            Step::new("b", "true", Vec::new(), false).with_needs(vec!["a"]),
+            Step::new("c", "true", Vec::new(), false),
+        ];
+
+        let (states, _) = run_steps(&steps, 1);
+
+        assert!(matches!(states[0], StepState::Finished(_)));
+        assert!(matches!(states[1], StepState::Skipped));
+        assert!(matches!(states[2], StepState::Skipped));
+    }
+
+    #[test]
+    fn test_continue_on_error_satisfies_needs() {
+        let steps = vec![
+            Step::new("a", "exit 1", Vec::new(), true),
+            Step::new("b", "true", Vec::new(), false).with_needs(vec!["a"]),
+        ];
+
+        let (states, _) = run_steps(&steps, 2);
+
+        assert!(matches!(&states[1], StepState::Finished(captured) if captured.success()));
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use base64::Engine;
+use chrono::{format::StrftimeItems, DateTime, Local, NaiveDate, NaiveDateTime};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson,
+};
+use serde_json::Value;
+
+use crate::prelude::{home_dir, Error, WorkflowShell};
+
+/// The helpers of the templates of commands, on top of the built-in ones of Handlebars.
+pub const TEMPLATE_HELPERS: [&str; 16] = [
+    "quote",
+    "raw",
+    "env",
+    "default",
+    "upper",
+    "lower",
+    "snake",
+    "kebab",
+    "expand_home",
+    "basename",
+    "dirname",
+    "join",
+    "now",
+    "date",
+    "uuid",
+    "base64",
+];
+
+/// The format of `now` and `date` without any, e.g. `2024-05-01T14:30:00+02:00`.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// The helpers of the templates written by the user as Rhai scripts, e.g. `slug.rhai` for
+/// `{{slug name}}`. The parameters of a helper are in `params` and its hash in `hash`, and
+/// the value of the script is inserted as the value of an argument is.
+#[derive(Default)]
+pub struct ScriptHelpers {
+    scripts: Vec<(String, PathBuf)>,
+    /// The renderers built so far, one per shell.
+    renderers: Mutex<HashMap<WorkflowShell, Arc<Handlebars<'static>>>>,
+}
+
+impl ScriptHelpers {
+    /// The scripts of the given directory, none if it does not exist.
+    pub fn new(directory: &Path) -> Self {
+        let mut scripts = std::fs::read_dir(directory)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == "rhai")
+                    })
+                    .filter_map(|path| {
+                        let name = path.file_stem()?.to_str()?.to_string();
+                        Some((name, path))
+                    })
+                    .collect::<Vec<(String, PathBuf)>>()
+            })
+            .unwrap_or_default();
+        scripts.sort();
+
+        Self {
+            scripts,
+            renderers: Mutex::default(),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.scripts.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The renderer of the commands run by the given shell, only built the first time since
+    /// building it reads and compiles every script.
+    ///
+    /// # Returns
+    /// * The renderer or an `Error` if a script is invalid or named after a built-in helper
+    pub fn renderer(&self, shell: WorkflowShell) -> Result<Arc<Handlebars<'static>>, Error> {
+        let mut renderers = self.renderers.lock().unwrap_or_else(|e| e.into_inner());
+        match renderers.entry(shell) {
+            Entry::Occupied(entry) => Ok(Arc::clone(entry.get())),
+            Entry::Vacant(entry) => Ok(Arc::clone(entry.insert(Arc::new(renderer(shell, self)?)))),
+        }
+    }
+}
+
+/// The Handlebars instance rendering commands run by the given shell. The values it inserts
+/// are marked for `WorkflowShell::escape_values`, except with `quote` and `raw`.
+///
+/// # Returns
+/// * The instance or an `Error` if a script helper is invalid or named after a built-in one
+pub fn renderer(
+    shell: WorkflowShell,
+    scripts: &ScriptHelpers,
+) -> Result<Handlebars<'static>, Error> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(WorkflowShell::mark);
+    handlebars.register_helper(
+        "quote",
+        Box::new(
+            move |helper: &Helper,
+                  _: &Handlebars,
+                  _: &Context,
+                  _: &mut RenderContext,
+                  out: &mut dyn Output|
+                  -> HelperResult {
+                out.write(&shell.quote(&text(helper, 0)?))?;
+                Ok(())
+            },
+        ),
+    );
+    handlebars.register_helper(
+        "raw",
+        Box::new(
+            |helper: &Helper,
+             _: &Handlebars,
+             _: &Context,
+             _: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                out.write(&text(helper, 0)?)?;
+                Ok(())
+            },
+        ),
+    );
+
+    let helpers: [(&str, ValueFn); 14] = [
+        ("env", env),
+        ("default", default),
+        ("upper", |helper| Ok(text(helper, 0)?.to_uppercase().into())),
+        ("lower", |helper| Ok(text(helper, 0)?.to_lowercase().into())),
+        ("snake", |helper| {
+            Ok(words(&text(helper, 0)?).join("_").into())
+        }),
+        ("kebab", |helper| {
+            Ok(words(&text(helper, 0)?).join("-").into())
+        }),
+        ("expand_home", expand_home),
+        ("basename", basename),
+        ("dirname", dirname),
+        ("join", join),
+        ("now", now),
+        ("date", date),
+        ("uuid", |_| Ok(uuid::Uuid::new_v4().to_string().into())),
+        ("base64", |helper| {
+            let value = text(helper, 0)?;
+            Ok(base64::engine::general_purpose::STANDARD
+                .encode(value)
+                .into())
+        }),
+    ];
+    for (name, helper) in helpers {
+        handlebars.register_helper(name, Box::new(ValueHelper(helper)));
+    }
+
+    for (name, path) in &scripts.scripts {
+        if TEMPLATE_HELPERS.contains(&name.as_str()) {
+            return Err(Error::ParseError(Some(
+                format!("The helper `{}` of `{}` is built in", name, path.display()).into(),
+            )));
+        }
+        handlebars
+            .register_script_helper_file(name, path)
+            .map_err(|e| {
+                Error::ParseError(Some(
+                    format!("Invalid helper `{}` of `{}`: {}", name, path.display(), e).into(),
+                ))
+            })?;
+    }
+
+    Ok(handlebars)
+}
+
+type ValueFn = fn(&Helper) -> Result<Value, RenderError>;
+
+/// A helper computing a value out of its parameters, inserted as the value of an argument is.
+struct ValueHelper(ValueFn);
+
+impl HelperDef for ValueHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        helper: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        (self.0)(helper).map(ScopedJson::Derived)
+    }
+}
+
+/// The parameter at the given index, as text. Something that is not set is empty.
+fn text(helper: &Helper, index: usize) -> Result<String, RenderError> {
+    let value = helper
+        .param(index)
+        .ok_or_else(|| RenderErrorReason::ParamNotFoundForIndex(helper_name(helper), index))?
+        .value();
+
+    Ok(to_text(value))
+}
+
+/// The name of a built-in helper, the only ones using `text`.
+fn helper_name(helper: &Helper) -> &'static str {
+    TEMPLATE_HELPERS
+        .into_iter()
+        .find(|name| *name == helper.name())
+        .unwrap_or("helper")
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// `{{env "NAME"}}`, the value of an environment variable, empty if it is not set.
+fn env(helper: &Helper) -> Result<Value, RenderError> {
+    Ok(std::env::var(text(helper, 0)?).unwrap_or_default().into())
+}
+
+/// `{{default name "value"}}`, the value unless it is empty.
+fn default(helper: &Helper) -> Result<Value, RenderError> {
+    let value = text(helper, 0)?;
+    match value.is_empty() {
+        true => Ok(text(helper, 1)?.into()),
+        false => Ok(value.into()),
+    }
+}
+
+/// The lowercase words of the value, split at anything but letters and digits, and where the
+/// case changes, e.g. `http`, `server` and `2` for `HTTPServer 2`.
+fn words(value: &str) -> Vec<String> {
+    let chars = value.chars().collect::<Vec<char>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (index, &char) in chars.iter().enumerate() {
+        if !char.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        let previous = index.checked_sub(1).map(|index| chars[index]);
+        let next = chars.get(index + 1);
+        // `fooBar` and `HTTPServer` split before the capital starting the new word
+        let is_boundary = char.is_uppercase()
+            && previous.is_some_and(|previous| {
+                previous.is_lowercase()
+                    || previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase())
+            });
+        if is_boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(char.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// `{{expand_home path}}`, the path with a leading `~` replaced by the home directory.
+fn expand_home(helper: &Helper) -> Result<Value, RenderError> {
+    let path = text(helper, 0)?;
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home_dir(), rest)
+        }
+        _ => path,
+    };
+
+    Ok(expanded.into())
+}
+
+/// `{{basename path}}`, the last component of the path.
+fn basename(helper: &Helper) -> Result<Value, RenderError> {
+    let path = text(helper, 0)?;
+    let name = Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(name.into())
+}
+
+/// `{{dirname path}}`, the path without its last component, `.` if there is nothing left.
+fn dirname(helper: &Helper) -> Result<Value, RenderError> {
+    let path = text(helper, 0)?;
+    let parent = match Path::new(&path).parent() {
+        Some(parent) if parent.as_os_str().is_empty() => ".".to_string(),
+        Some(parent) => parent.display().to_string(),
+        None => path,
+    };
+
+    Ok(parent.into())
+}
+
+/// `{{join a b sep="-"}}`, the parameters, or the items of the lists among them, joined with
+/// `sep`, `/` by default.
+fn join(helper: &Helper) -> Result<Value, RenderError> {
+    let separator = helper
+        .hash_get("sep")
+        .map(|separator| to_text(separator.value()))
+        .unwrap_or("/".to_string());
+    let parts = helper
+        .params()
+        .iter()
+        .flat_map(|param| match param.value() {
+            Value::Array(items) => items.iter().map(to_text).collect(),
+            value => vec![to_text(value)],
+        })
+        .collect::<Vec<String>>();
+
+    Ok(parts.join(&separator).into())
+}
+
+/// `{{now}}` or `{{now "%Y-%m-%d"}}`, the current local time in the given `strftime` format.
+fn now(helper: &Helper) -> Result<Value, RenderError> {
+    let format = match helper.param(0) {
+        Some(_) => text(helper, 0)?,
+        None => DATE_FORMAT.to_string(),
+    };
+
+    format_date(&Local::now().fixed_offset(), &format)
+}
+
+/// `{{date value "%d/%m/%Y"}}`, a date given as RFC 3339, `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`
+/// or a Unix timestamp, in the given `strftime` format.
+fn date(helper: &Helper) -> Result<Value, RenderError> {
+    let value = text(helper, 0)?;
+    let format = match helper.param(1) {
+        Some(_) => text(helper, 1)?,
+        None => DATE_FORMAT.to_string(),
+    };
+    let local = |date: NaiveDateTime| date.and_local_timezone(Local).earliest();
+
+    let date = DateTime::parse_from_rfc3339(&value)
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .and_then(local)
+                .map(|date| date.fixed_offset())
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .and_then(local)
+                .map(|date| date.fixed_offset())
+        })
+        .or_else(|| {
+            value
+                .parse::<i64>()
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                .map(|date| date.with_timezone(&Local).fixed_offset())
+        })
+        .ok_or_else(|| RenderErrorReason::Other(format!("`date` cannot read `{}`", value)))?;
+
+    format_date(&date, &format)
+}
+
+fn format_date(date: &DateTime<chrono::FixedOffset>, format: &str) -> Result<Value, RenderError> {
+    // An invalid format would only fail once the date is written
+    let items = StrftimeItems::new(format)
+        .parse()
+        .map_err(|_| RenderErrorReason::Other(format!("Invalid date format `{}`", format)))?;
+
+    Ok(date.format_with_items(items.iter()).to_string().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn render(template: &str) -> String {
+        let arguments = HashMap::from([
+            ("name", "HTTPServer fooBar-baz 2"),
+            ("path", "/var/log/app.log"),
+            ("empty", ""),
+        ]);
+
+        let command = renderer(WorkflowShell::Sh, &ScriptHelpers::default())
+            .unwrap()
+            .render_template(template, &arguments)
+            .unwrap();
+
+        WorkflowShell::Sh.escape_values(&command).unwrap()
+    }
+
+    #[test]
+    fn test_case_helpers() {
+        assert_eq!(render("{{upper 'ab'}}"), "AB");
+        assert_eq!(render("{{lower 'AB'}}"), "ab");
+        assert_eq!(render("{{snake name}}"), "http_server_foo_bar_baz_2");
+        assert_eq!(render("{{kebab name}}"), "http-server-foo-bar-baz-2");
+    }
+
+    #[test]
+    fn test_value_helpers() {
+        std::env::set_var("WORKFLOW_TEMPLATE_TEST", "set");
+
+        assert_eq!(render("{{env 'WORKFLOW_TEMPLATE_TEST'}}"), "set");
+        assert_eq!(render("{{default empty 'fallback'}}"), "fallback");
+        assert_eq!(render("{{default path 'fallback'}}"), "/var/log/app.log");
+        assert_eq!(render("{{basename path}}"), "app.log");
+        assert_eq!(render("{{dirname path}}"), "/var/log");
+        assert_eq!(render("{{dirname 'app.log'}}"), ".");
+        assert_eq!(render("{{join 'a' 'b' sep='-'}}"), "a-b");
+        assert_eq!(
+            render("{{join (dirname path) 'other.log'}}"),
+            "/var/log/other.log"
+        );
+        assert_eq!(render("{{base64 'workflow'}}"), "'d29ya2Zsb3c='");
+        assert_eq!(
+            render("{{expand_home '~/.ssh'}}"),
+            format!("{}/.ssh", home_dir())
+        );
+        assert_eq!(render("{{uuid}}").len(), 36);
+    }
+
+    #[test]
+    fn test_date_helpers() {
+        assert_eq!(render("{{date '2024-05-01' '%d/%m/%Y'}}"), "01/05/2024");
+        assert_eq!(
+            render("{{date '2024-05-01T10:00:00+02:00' '%H:%M %z'}}"),
+            "'10:00 +0200'"
+        );
+        assert_eq!(
+            render("{{now '%Y'}}"),
+            Local::now().format("%Y").to_string()
+        );
+        assert!(renderer(WorkflowShell::Sh, &ScriptHelpers::default())
+            .unwrap()
+            .render_template("{{now '%Q'}}", &())
+            .is_err());
+    }
+
+    #[test]
+    fn test_helpers_are_quoted() {
+        // Whatever a helper returns is a single word, unless it is inserted as is
+        assert_eq!(render("echo {{upper 'a; b'}}"), "echo 'A; B'");
+        assert_eq!(render("echo {{raw (upper 'a; b')}}"), "echo A; B");
+    }
+
+    #[test]
+    fn test_script_helpers() {
+        let directory = std::env::temp_dir().join("workflow_test_script_helpers");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("greet.rhai"),
+            r#"`Hello, ${params[0]}${hash.punctuation ?? "!"}`"#,
+        )
+        .unwrap();
+        std::fs::write(directory.join("notes.txt"), "not a helper").unwrap();
+
+        let scripts = ScriptHelpers::new(&directory);
+        let renderer = scripts.renderer(WorkflowShell::Sh).unwrap();
+        let rendered = renderer
+            .render_template("echo {{greet name}}", &HashMap::from([("name", "Jane")]))
+            .map(|command| WorkflowShell::Sh.escape_values(&command).unwrap());
+        // The scripts are only read once per shell
+        std::fs::write(directory.join("greet.rhai"), "params[0]").unwrap();
+        let is_reused = Arc::ptr_eq(&renderer, &scripts.renderer(WorkflowShell::Sh).unwrap())
+            && !Arc::ptr_eq(&renderer, &scripts.renderer(WorkflowShell::Bash).unwrap());
+
+        std::fs::write(directory.join("upper.rhai"), "params[0]").unwrap();
+        let is_clashing = ScriptHelpers::new(&directory)
+            .renderer(WorkflowShell::Sh)
+            .is_err();
+        std::fs::remove_dir_all(&directory).unwrap_or_default();
+
+        assert_eq!(scripts.names().collect::<Vec<&str>>(), vec!["greet"]);
+        assert_eq!(rendered.unwrap(), "echo 'Hello, Jane!'");
+        assert!(is_reused);
+        assert!(is_clashing);
+    }
+}
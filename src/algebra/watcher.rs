@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeSet,
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::prelude::Error;
+
+/// How long the files have to stay untouched before a change is reported, so that saving
+/// several files at once or an editor writing a file in steps is a single change.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches the files matching a set of globs, e.g. `src/**/*.rs`, relative to a directory.
+pub struct Watcher {
+    root: PathBuf,
+    globs: GlobSet,
+    events: Receiver<notify::Result<notify::Event>>,
+    // Stops watching once dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Watch the files matching any of the given globs. A relative glob is relative to `root`
+    /// and `*` does not match `/`, unlike `**`.
+    ///
+    /// # Returns
+    /// * The `Watcher` or an `Error` if a glob is invalid or its directory cannot be watched
+    pub fn new(root: &Path, patterns: &[String]) -> Result<Self, Error> {
+        let globs = patterns
+            .iter()
+            .try_fold(GlobSetBuilder::new(), |mut builder, pattern| {
+                let glob = GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| {
+                        Error::InvalidArguments(Some(
+                            format!("Invalid watch pattern `{}`: {}", pattern, e).into(),
+                        ))
+                    })?;
+                builder.add(glob);
+                Ok::<GlobSetBuilder, Error>(builder)
+            })?
+            .build()
+            .map_err(|e| Error::InvalidArguments(Some(e.into())))?;
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).map_err(|e| Error::Io(Some(e.into())))?;
+        let directories = patterns
+            .iter()
+            .map(|pattern| watched_directory(root, pattern))
+            .collect::<BTreeSet<PathBuf>>();
+        for directory in &directories {
+            // A directory inside another one is already watched
+            if directories
+                .iter()
+                .any(|other| other != directory && directory.starts_with(other))
+            {
+                continue;
+            }
+            watcher
+                .watch(directory, RecursiveMode::Recursive)
+                .map_err(|e| Error::Io(Some(e.into())))?;
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            globs,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Whether the given path matches any of the globs, relative to the root or as is.
+    pub fn matches(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.root)
+            .is_ok_and(|relative| self.globs.is_match(relative))
+            || self.globs.is_match(path)
+    }
+
+    /// Wait at most `timeout` for a matching file to be created, modified or removed, then until
+    /// no other one is for `DEBOUNCE`.
+    ///
+    /// # Returns
+    /// * The files that changed, in order, or `None` if none did within `timeout`
+    pub fn wait(&self, timeout: Duration) -> Option<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        let mut timeout = timeout;
+
+        loop {
+            match self.events.recv_timeout(timeout) {
+                Ok(Ok(event)) => {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) {
+                        changed.extend(event.paths.into_iter().filter(|path| self.matches(path)));
+                    }
+                }
+                // An event that could not be read is not a change of a matching file
+                Ok(Err(_)) => {}
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                    return (!changed.is_empty()).then(|| changed.into_iter().collect());
+                }
+            }
+
+            if !changed.is_empty() {
+                timeout = DEBOUNCE;
+            }
+        }
+    }
+}
+
+/// The deepest existing directory holding every file the glob can match, e.g. `root/src` for
+/// `src/**/*.rs`.
+fn watched_directory(root: &Path, pattern: &str) -> PathBuf {
+    let literal = Path::new(pattern)
+        .components()
+        .take_while(|component| match component {
+            Component::Normal(part) => !part
+                .to_string_lossy()
+                .contains(['*', '?', '[', ']', '{', '}']),
+            _ => true,
+        })
+        .collect::<PathBuf>();
+
+    root.join(literal)
+        .ancestors()
+        .find(|directory| directory.is_dir())
+        .map(Path::to_path_buf)
+        .unwrap_or(root.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_watched_directory() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+        assert_eq!(watched_directory(root, "src/**/*.rs"), root.join("src"));
+        assert_eq!(
+            watched_directory(root, "src/algebra/*.rs"),
+            root.join("src/algebra")
+        );
+        assert_eq!(watched_directory(root, "Cargo.toml"), root);
+        assert_eq!(watched_directory(root, "not-there/*.txt"), root);
+    }
+
+    #[test]
+    fn test_matches_relative_to_root() {
+        let root = std::env::temp_dir().join("workflow_test_watcher_matches");
+        fs::create_dir_all(&root).unwrap();
+        let watcher = Watcher::new(&root, &["src/*.rs".to_string()]).unwrap();
+
+        assert!(watcher.matches(&root.join("src/main.rs")));
+        assert!(!watcher.matches(&root.join("src/domain/mod.rs")));
+        assert!(!watcher.matches(&root.join("README.md")));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let watcher = Watcher::new(&std::env::temp_dir(), &["src/[*.rs".to_string()]);
+
+        assert!(matches!(watcher, Err(Error::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn test_wait_for_changes() {
+        let root = std::env::temp_dir().join("workflow_test_watcher_wait");
+        fs::create_dir_all(&root).unwrap();
+        let watcher = Watcher::new(&root, &["*.txt".to_string()]).unwrap();
+
+        fs::write(root.join("ignored.log"), "ignored").unwrap();
+        assert_eq!(watcher.wait(Duration::from_millis(300)), None);
+
+        fs::write(root.join("notes.txt"), "one").unwrap();
+        fs::write(root.join("notes.txt"), "two").unwrap();
+        let changed = watcher.wait(Duration::from_secs(5));
+        fs::remove_dir_all(&root).unwrap_or_default();
+
+        assert_eq!(changed, Some(vec![root.join("notes.txt")]));
+    }
+}
@@ -123,6 +123,20 @@ pub struct Argument {
     /// The values that the argument can take
     #[serde(default = "Vec::new")]
     values: Vec<ArgumentValue>,
+    /// Whether the values given to the argument are remembered and suggested in later runs
+    #[serde(default = "remember_by_default", skip_serializing_if = "is_true")]
+    remember: bool,
+    /// Whether the argument takes several values, running the workflow once per value
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    repeat: bool,
+}
+
+fn remember_by_default() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
 }
 
 impl Argument {
@@ -137,6 +151,8 @@ impl Argument {
             description: description.map(|d| ArgumentDescription(d.to_string())),
             default: default.map(|d| ArgumentDefault(d.to_string())),
             values,
+            remember: true,
+            repeat: false,
         }
     }
 
@@ -150,9 +166,23 @@ impl Argument {
                 .into_iter()
                 .map(|v| ArgumentValue(v.to_string()))
                 .collect(),
+            remember: true,
+            repeat: false,
         }
     }
 
+    /// Remember the values given to the argument, or not, e.g. for secrets.
+    pub fn with_remember(mut self, remember: bool) -> Self {
+        self.remember = remember;
+        self
+    }
+
+    /// Let the argument take several values, or not.
+    pub fn with_repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
     pub fn name(&self) -> &ArgumentName {
         &self.name
     }
@@ -172,4 +202,12 @@ impl Argument {
     pub fn values(&self) -> &Vec<ArgumentValue> {
         &self.values
     }
+
+    pub fn remember(&self) -> bool {
+        self.remember
+    }
+
+    pub fn repeat(&self) -> bool {
+        self.repeat
+    }
 }
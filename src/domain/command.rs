@@ -1,13 +1,70 @@
-use clap::Parser;
+use std::num::NonZeroUsize;
+
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, propagate_version = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Answer yes to every confirmation, the one of dangerous commands included"
+    )]
+    yes: bool,
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        default_value_t = OutputFormat::Text,
+        help = "How the result is printed, `json` printing a single document to stdout"
+    )]
+    output: OutputFormat,
+}
+
+impl Cli {
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+
+    pub fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    pub fn into_command(self) -> Command {
+        self.command
+    }
+}
+
+/// How the result of a subcommand is printed.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Coloured text meant to be read in a terminal
+    #[default]
+    Text,
+    /// A JSON document on stdout, while everything else is written to stderr
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
 pub enum Command {
     Run(Run),
+    Pipe(Pipe),
     List(List),
     Search(Search),
     Reset(Reset),
     Create(Create),
+    Init(Init),
+    History(History),
+    Rerun(Rerun),
+    Jobs(Jobs),
+    Logs(Logs),
+    Kill(Kill),
+    #[command(name = "scheduler")]
+    Daemon(Daemon),
+    RunJob(RunJob),
 }
 
 #[derive(Parser, Debug, Default)]
@@ -19,39 +76,357 @@ pub struct List;
 pub struct Run {
     #[arg(short, long, help = "The name of the workflow")]
     name: String,
+    #[arg(
+        long = "arg",
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        help = "Set the value of an argument, can be repeated"
+    )]
+    arguments: Vec<(String, String)>,
+    #[arg(
+        long = "args-json",
+        value_name = "FILE",
+        help = "Read argument values from a JSON object in a file, or `-` for stdin"
+    )]
+    arguments_json: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ExecutionMode::Pipe,
+        help = "How the standard streams of the command are handled"
+    )]
+    mode: ExecutionMode,
+    #[arg(
+        long,
+        value_name = "FD",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        help = "Print the rendered command instead of executing it, to stdout or the given file descriptor"
+    )]
+    print: Option<i32>,
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        help = "The maximum number of steps running at the same time, overriding `concurrency`"
+    )]
+    jobs: Option<NonZeroUsize>,
+    #[arg(
+        short,
+        long,
+        conflicts_with = "print",
+        help = "Run the workflow in the background, see `workflow jobs`"
+    )]
+    detach: bool,
+    #[arg(
+        short,
+        long,
+        value_name = "GLOB",
+        num_args = 1..,
+        conflicts_with_all = ["print", "detach"],
+        help = "Run the workflow again every time a file matching the glob changes, overriding `watch`"
+    )]
+    watch: Vec<String>,
+    #[arg(
+        long,
+        help = "Only inherit the environment variables needed to run anything, like `PATH`, overriding `env_policy`"
+    )]
+    clean_env: bool,
+    #[arg(skip = true)]
+    watch_key: bool,
+    #[arg(skip)]
+    yes: bool,
 }
 
 impl Run {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            arguments: Vec::new(),
+            arguments_json: None,
+            mode: ExecutionMode::default(),
+            print: None,
+            jobs: None,
+            detach: false,
+            watch: Vec::new(),
+            clean_env: false,
+            watch_key: true,
+            yes: false,
         }
     }
 
+    /// Use the given argument values instead of prompting for them.
+    pub fn with_arguments(mut self, arguments: Vec<(String, String)>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    /// Execute the command without asking for confirmation, even when it looks dangerous.
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    /// Run the workflow with the given mode, the way its standard streams are wired.
+    pub fn with_mode(mut self, mode: ExecutionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Run at most the given number of steps at the same time.
+    pub fn with_jobs(mut self, jobs: Option<NonZeroUsize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Only inherit the essential environment variables, whatever the `env_policy`.
+    pub fn with_clean_env(mut self, clean_env: bool) -> Self {
+        self.clean_env = clean_env;
+        self
+    }
+
+    /// Whether the `watch` key of the workflow is followed, which never ends the run. Only an
+    /// interactive run does, `--watch` being followed anyway.
+    pub fn with_watch_key(mut self, watch_key: bool) -> Self {
+        self.watch_key = watch_key;
+        self
+    }
+
+    /// Print the rendered command to the given file descriptor instead of executing it.
+    pub fn with_print(mut self, print: Option<i32>) -> Self {
+        self.print = print;
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    pub fn arguments(&self) -> &Vec<(String, String)> {
+        &self.arguments
+    }
+
+    pub fn arguments_json(&self) -> Option<&str> {
+        self.arguments_json.as_deref()
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn print(&self) -> Option<i32> {
+        self.print
+    }
+
+    pub fn jobs(&self) -> Option<NonZeroUsize> {
+        self.jobs
+    }
+
+    pub fn detach(&self) -> bool {
+        self.detach
+    }
+
+    pub fn watch(&self) -> &[String] {
+        &self.watch
+    }
+
+    pub fn clean_env(&self) -> bool {
+        self.clean_env
+    }
+
+    pub fn watch_key(&self) -> bool {
+        self.watch_key
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Pipe the output of a workflow into the next one, e.g. `workflow pipe list_pods describe_pod`"
+)]
+pub struct Pipe {
+    #[arg(
+        value_name = "NAME",
+        num_args = 2..,
+        required = true,
+        help = "The names of the workflows, the output of each one being the input of the next one"
+    )]
+    names: Vec<String>,
+    #[arg(
+        long = "arg",
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        help = "Set the value of an argument of the workflows having it, can be repeated"
+    )]
+    arguments: Vec<(String, String)>,
+    #[arg(
+        long = "args-json",
+        value_name = "FILE",
+        help = "Read argument values from a JSON object in a file"
+    )]
+    arguments_json: Option<String>,
+    #[arg(
+        long,
+        help = "Only inherit the environment variables needed to run anything, like `PATH`, overriding `env_policy`"
+    )]
+    clean_env: bool,
+    #[arg(skip)]
+    yes: bool,
+}
+
+impl Pipe {
+    pub fn new(names: Vec<String>) -> Self {
+        Self {
+            names,
+            arguments: Vec::new(),
+            arguments_json: None,
+            clean_env: false,
+            yes: false,
+        }
+    }
+
+    /// Use the given argument values instead of prompting for them.
+    pub fn with_arguments(mut self, arguments: Vec<(String, String)>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    /// Execute the pipeline without asking for confirmation, even when it looks dangerous.
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn arguments(&self) -> &Vec<(String, String)> {
+        &self.arguments
+    }
+
+    pub fn arguments_json(&self) -> Option<&str> {
+        self.arguments_json.as_deref()
+    }
+
+    pub fn clean_env(&self) -> bool {
+        self.clean_env
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+}
+
+/// How the standard streams of an executed command are wired.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// The command shares the terminal with the CLI and nothing is captured
+    Inherit,
+    /// The output is streamed live while a copy of it is captured
+    #[default]
+    Pipe,
+    /// The command runs in a pseudo-terminal, for interactive tools like `ssh` or `git rebase -i`
+    Pty,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Print the shell integration, e.g. `eval \"$(workflow init zsh)\"`",
+    long_about = "Print the shell integration, e.g. `eval \"$(workflow init zsh)\"`. It binds Ctrl-G to search for a workflow and insert its rendered command into the prompt, so it can be edited before running it."
+)]
+pub struct Init {
+    #[arg(value_enum, help = "The shell to integrate with")]
+    shell: InitShell,
+}
+
+impl Init {
+    pub fn new(shell: InitShell) -> Self {
+        Self { shell }
+    }
+
+    pub fn shell(&self) -> InitShell {
+        self.shell
+    }
+}
+
+/// The shells `workflow init` can integrate with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Parse a `key=value` pair as given to `--arg`.
+fn parse_key_value(input: &str) -> Result<(String, String), String> {
+    input
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{}`", input))
 }
 
 #[derive(Parser, Debug, Clone, Default)]
 #[command(about = "Search for workflows, e.g. `workflow search`")]
-pub struct Search;
+pub struct Search {
+    #[arg(
+        long,
+        value_name = "FD",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        help = "Print the rendered command instead of executing it, to stdout or the given file descriptor"
+    )]
+    print: Option<i32>,
+    #[arg(skip)]
+    yes: bool,
+}
 
 impl Search {
     #[cfg(test)]
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Execute the found workflow without asking for confirmation.
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+
+    pub fn print(&self) -> Option<i32> {
+        self.print
     }
 }
 
 #[derive(Parser, Debug, Default)]
 #[command(about = "Reset the workflow store, e.g. `workflow reset`")]
-pub struct Reset;
+pub struct Reset {
+    #[arg(skip)]
+    yes: bool,
+}
 
 impl Reset {
     #[cfg(test)]
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Reset the workflows without asking for confirmation.
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
     }
 }
 
@@ -65,3 +440,453 @@ impl Create {
         Self {}
     }
 }
+
+#[derive(Parser, Debug, Default)]
+#[command(about = "Show the past runs of workflows, e.g. `workflow history --name <name>`")]
+pub struct History {
+    #[arg(short, long, help = "Only show the runs of this workflow")]
+    name: Option<String>,
+    #[arg(short, long, value_enum, help = "Only show the runs with this outcome")]
+    status: Option<HistoryStatus>,
+    #[arg(
+        short,
+        long,
+        default_value_t = 20,
+        help = "The maximum number of runs to show"
+    )]
+    limit: usize,
+}
+
+impl History {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn status(&self) -> Option<HistoryStatus> {
+        self.status
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+/// The outcome of a past run, to filter the history with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryStatus {
+    /// The run exited with code 0
+    Success,
+    /// The run exited with any other code
+    Failure,
+}
+
+#[derive(Parser, Debug, Default)]
+#[command(
+    about = "Run a past invocation again without prompting for its arguments, e.g. `workflow rerun 2` or `workflow rerun --last`"
+)]
+pub struct Rerun {
+    #[arg(
+        value_name = "N",
+        conflicts_with = "last",
+        help = "The number of the run in `workflow history`, 1 being the most recent"
+    )]
+    number: Option<NonZeroUsize>,
+    #[arg(long, help = "Run the most recent invocation again, the default")]
+    last: bool,
+    #[arg(skip)]
+    yes: bool,
+}
+
+impl Rerun {
+    /// Run the invocation again without asking for confirmation.
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+
+    /// The number of the run in the history, 1 being the most recent.
+    pub fn number(&self) -> NonZeroUsize {
+        self.number.unwrap_or(NonZeroUsize::MIN)
+    }
+}
+
+#[derive(Parser, Debug, Default)]
+#[command(about = "List the jobs started with `workflow run --detach`, e.g. `workflow jobs`")]
+pub struct Jobs;
+
+#[derive(Parser, Debug)]
+#[command(about = "Print the output of a job, e.g. `workflow logs 3 --follow`")]
+pub struct Logs {
+    #[arg(
+        value_name = "JOB",
+        help = "The number of the job, as listed by `workflow jobs`"
+    )]
+    job: u64,
+    #[arg(short, long, help = "Keep printing the output until the job is done")]
+    follow: bool,
+}
+
+impl Logs {
+    pub fn job(&self) -> u64 {
+        self.job
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Stop a running job, e.g. `workflow kill 3`")]
+pub struct Kill {
+    #[arg(
+        value_name = "JOB",
+        help = "The number of the job, as listed by `workflow jobs`"
+    )]
+    job: u64,
+}
+
+impl Kill {
+    pub fn job(&self) -> u64 {
+        self.job
+    }
+}
+
+#[derive(Parser, Debug, Default)]
+#[command(
+    about = "Run the workflows with a `schedule` whenever they are due, until stopped, e.g. `workflow scheduler`"
+)]
+pub struct Daemon {
+    #[arg(
+        short,
+        long,
+        help = "List the schedules with their next and last runs instead of running them"
+    )]
+    list: bool,
+}
+
+impl Daemon {
+    pub fn list(&self) -> bool {
+        self.list
+    }
+}
+
+/// Runs a job in the process started by `workflow run --detach`, which is not meant to be
+/// called by hand.
+#[derive(Parser, Debug)]
+#[command(hide = true)]
+pub struct RunJob {
+    #[arg(value_name = "JOB")]
+    job: u64,
+    #[arg(long, value_enum, default_value_t = ExecutionMode::Pipe)]
+    mode: ExecutionMode,
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+    #[arg(long)]
+    clean_env: bool,
+}
+
+impl RunJob {
+    pub fn new(run: &Run, job: u64) -> Self {
+        Self {
+            job,
+            mode: run.mode(),
+            jobs: run.jobs(),
+            clean_env: run.clean_env(),
+        }
+    }
+
+    /// The arguments to call `workflow` with to run this job.
+    pub fn to_args(&self) -> Vec<String> {
+        let mode = self
+            .mode
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        let mut args = vec![
+            "run-job".to_string(),
+            self.job.to_string(),
+            "--mode".to_string(),
+            mode,
+        ];
+        if let Some(jobs) = self.jobs {
+            args.extend(["--jobs".to_string(), jobs.to_string()]);
+        }
+        if self.clean_env {
+            args.push("--clean-env".to_string());
+        }
+        args
+    }
+
+    pub fn job(&self) -> u64 {
+        self.job
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn jobs(&self) -> Option<NonZeroUsize> {
+        self.jobs
+    }
+
+    pub fn clean_env(&self) -> bool {
+        self.clean_env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_with_arguments() {
+        let command = Cli::parse_from([
+            "workflow",
+            "run",
+            "--name",
+            "echo",
+            "--arg",
+            "userName=Jane Doe",
+            "--arg",
+            "query=a=b",
+        ])
+        .into_command();
+
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+
+        assert_eq!(run.name(), "echo");
+        assert_eq!(
+            run.arguments(),
+            &vec![
+                ("userName".to_string(), "Jane Doe".to_string()),
+                ("query".to_string(), "a=b".to_string()),
+            ]
+        );
+        assert_eq!(run.arguments_json(), None);
+        assert_eq!(run.mode(), ExecutionMode::Pipe);
+    }
+
+    #[test]
+    fn test_run_with_mode() {
+        let command =
+            Cli::parse_from(["workflow", "run", "--name", "echo", "--mode", "pty"]).into_command();
+
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+
+        assert_eq!(run.mode(), ExecutionMode::Pty);
+    }
+
+    #[test]
+    fn test_run_with_print() {
+        let command =
+            Cli::parse_from(["workflow", "run", "--print", "--name", "echo"]).into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+        assert_eq!(run.print(), Some(1));
+
+        let command =
+            Cli::parse_from(["workflow", "run", "--name", "echo", "--print", "3"]).into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+        assert_eq!(run.print(), Some(3));
+    }
+
+    #[test]
+    fn test_run_with_jobs() {
+        let command =
+            Cli::parse_from(["workflow", "run", "--name", "echo", "-j", "4"]).into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+        assert_eq!(run.jobs(), NonZeroUsize::new(4));
+
+        let command = Cli::try_parse_from(["workflow", "run", "--name", "echo", "--jobs", "0"]);
+        assert!(command.is_err());
+    }
+
+    #[test]
+    fn test_history_with_filters() {
+        let command = Cli::parse_from([
+            "workflow", "history", "--name", "echo", "--status", "failure",
+        ])
+        .into_command();
+        let Command::History(history) = command else {
+            panic!("Expected a history command");
+        };
+
+        assert_eq!(history.name(), Some("echo"));
+        assert_eq!(history.status(), Some(HistoryStatus::Failure));
+        assert_eq!(history.limit(), 20);
+    }
+
+    #[test]
+    fn test_run_detached() {
+        let command =
+            Cli::parse_from(["workflow", "run", "--name", "echo", "--detach"]).into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+        assert!(run.detach());
+
+        assert!(
+            Cli::try_parse_from(["workflow", "run", "--name", "echo", "-d", "--print"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_run_watched() {
+        let command = Cli::parse_from([
+            "workflow",
+            "run",
+            "--name",
+            "test",
+            "--watch",
+            "src/**/*.rs",
+            "Cargo.toml",
+        ])
+        .into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+        assert_eq!(run.watch(), ["src/**/*.rs", "Cargo.toml"]);
+
+        assert!(
+            Cli::try_parse_from(["workflow", "run", "--name", "test", "-d", "-w", "*.rs"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let command = Cli::parse_from(["workflow", "scheduler", "--list"]).into_command();
+        let Command::Daemon(daemon) = command else {
+            panic!("Expected a scheduler command");
+        };
+
+        assert!(daemon.list());
+    }
+
+    #[test]
+    fn test_run_job_round_trips_its_args() {
+        let run = Run::new("deploy")
+            .with_mode(ExecutionMode::Pty)
+            .with_jobs(NonZeroUsize::new(2))
+            .with_clean_env(true);
+        let run_job = RunJob::new(&run, 3);
+        let args = std::iter::once("workflow".to_string()).chain(run_job.to_args());
+
+        let Command::RunJob(parsed) = Cli::parse_from(args).into_command() else {
+            panic!("Expected a run-job command");
+        };
+
+        assert_eq!(parsed.job(), 3);
+        assert_eq!(parsed.mode(), ExecutionMode::Pty);
+        assert_eq!(parsed.jobs(), NonZeroUsize::new(2));
+        assert!(parsed.clean_env());
+    }
+
+    #[test]
+    fn test_pipe() {
+        let Command::Pipe(pipe) =
+            Cli::parse_from(["workflow", "pipe", "list_pods", "describe_pod"]).into_command()
+        else {
+            panic!("Expected a pipe command");
+        };
+
+        assert_eq!(pipe.names(), ["list_pods", "describe_pod"]);
+        assert!(Cli::try_parse_from(["workflow", "pipe", "list_pods"]).is_err());
+
+        let Command::Pipe(pipe) = Cli::parse_from([
+            "workflow",
+            "pipe",
+            "list_pods",
+            "describe_pod",
+            "--arg",
+            "namespace=default",
+        ])
+        .into_command() else {
+            panic!("Expected a pipe command");
+        };
+        assert_eq!(
+            pipe.arguments(),
+            &vec![("namespace".to_string(), "default".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_logs() {
+        let Command::Logs(logs) = Cli::parse_from(["workflow", "logs", "3", "-f"]).into_command()
+        else {
+            panic!("Expected a logs command");
+        };
+
+        assert_eq!(logs.job(), 3);
+        assert!(logs.follow());
+    }
+
+    #[test]
+    fn test_yes_is_global() {
+        assert!(!Cli::parse_from(["workflow", "run", "--name", "echo"]).yes());
+        assert!(Cli::parse_from(["workflow", "--yes", "run", "--name", "echo"]).yes());
+        assert!(Cli::parse_from(["workflow", "run", "--name", "echo", "-y"]).yes());
+        assert!(Cli::parse_from(["workflow", "reset", "--yes"]).yes());
+        assert!(Cli::parse_from(["workflow", "rerun", "2", "--yes"]).yes());
+    }
+
+    #[test]
+    fn test_output_is_global() {
+        let cli = Cli::parse_from(["workflow", "list"]);
+        assert_eq!(cli.output(), OutputFormat::Text);
+
+        let cli = Cli::parse_from(["workflow", "history", "--output", "json"]);
+        assert_eq!(cli.output(), OutputFormat::Json);
+
+        assert!(Cli::try_parse_from(["workflow", "--output", "yaml", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_rerun() {
+        let command = Cli::parse_from(["workflow", "rerun", "3"]).into_command();
+        let Command::Rerun(rerun) = command else {
+            panic!("Expected a rerun command");
+        };
+        assert_eq!(rerun.number().get(), 3);
+
+        let command = Cli::parse_from(["workflow", "rerun", "--last"]).into_command();
+        let Command::Rerun(rerun) = command else {
+            panic!("Expected a rerun command");
+        };
+        assert_eq!(rerun.number().get(), 1);
+
+        assert!(Cli::try_parse_from(["workflow", "rerun", "3", "--last"]).is_err());
+    }
+
+    #[test]
+    fn test_init() {
+        let command = Cli::parse_from(["workflow", "init", "zsh"]).into_command();
+        let Command::Init(init) = command else {
+            panic!("Expected an init command");
+        };
+
+        assert_eq!(init.shell(), InitShell::Zsh);
+        assert!(Cli::try_parse_from(["workflow", "init", "csh"]).is_err());
+    }
+
+    #[test]
+    fn test_run_with_invalid_argument() {
+        let command =
+            Cli::try_parse_from(["workflow", "run", "--name", "echo", "--arg", "userName"]);
+
+        assert!(command.is_err());
+    }
+}
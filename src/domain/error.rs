@@ -1,5 +1,7 @@
 use std::error::Error as StdError;
 use std::fmt::{Debug, Formatter, Result};
+
+use serde_json::{json, Value};
 use thiserror::Error as ThisError;
 
 #[derive(ThisError)]
@@ -36,6 +38,50 @@ pub enum Error {
     Io(#[from] Option<Box<dyn StdError>>),
 }
 
+impl Error {
+    /// The code the CLI exits with when this error aborts it. These follow `sysexits.h`, so
+    /// they stay apart from the usual `1` and `2` of failing commands, but a command can exit
+    /// with them too, hence the `exit_source` of `to_json`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidArguments(_) => 64,
+            Error::InvalidName(_)
+            | Error::InvalidDescription(_)
+            | Error::InvalidCommand(_)
+            | Error::InvalidSource(_)
+            | Error::InvalidAuthor(_)
+            | Error::InvalidVersion(_)
+            | Error::InvalidTags(_)
+            | Error::ParseError(_) => 65,
+            Error::ReadError(_) => 66,
+            Error::StoreError(_) => 69,
+            Error::SchemaError(_) => 70,
+            Error::WriteError(_) => 73,
+            Error::Io(_) => 74,
+            Error::InvalidConfiguration(_) => 78,
+        }
+    }
+
+    /// The error as a JSON document, for `--output json`.
+    pub fn to_json(&self) -> Value {
+        let mut causes = Vec::new();
+        let mut current = self.source();
+
+        while let Some(cause) = current {
+            causes.push(cause.to_string());
+            current = cause.source();
+        }
+
+        json!({
+            "type": "error",
+            "message": self.to_string(),
+            "causes": causes,
+            "exit_code": self.exit_code(),
+            "exit_source": "workflow",
+        })
+    }
+}
+
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         writeln!(f, "{}\n", &self)?;
@@ -50,3 +96,24 @@ impl Debug for Error {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_to_json() {
+        let error = Error::InvalidArguments(Some("There is no job 9".into()));
+
+        assert_eq!(
+            error.to_json(),
+            json!({
+                "type": "error",
+                "message": "The workflow arguments are invalid.",
+                "causes": ["There is no job 9"],
+                "exit_code": 64,
+                "exit_source": "workflow",
+            })
+        );
+    }
+}
@@ -1,7 +1,180 @@
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+/// The exit status of an executed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitStatus {
+    code: Option<i32>,
+    signal: Option<i32>,
+    timed_out: bool,
+    interrupted: Option<i32>,
+}
+
+impl ExitStatus {
+    pub fn new(code: Option<i32>, signal: Option<i32>) -> Self {
+        Self {
+            code,
+            signal,
+            timed_out: false,
+            interrupted: None,
+        }
+    }
+
+    /// Mark the command as killed for running longer than its timeout.
+    pub fn into_timed_out(mut self) -> Self {
+        self.timed_out = true;
+        self
+    }
+
+    /// Whether the command was killed for running longer than its timeout.
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Mark the command as stopped because `workflow` received the given signal, which was
+    /// forwarded to it.
+    pub fn into_interrupted(mut self, signal: i32) -> Self {
+        self.interrupted = Some(signal);
+        self
+    }
+
+    /// The signal `workflow` received while the command ran, if any.
+    pub fn interrupted(&self) -> Option<i32> {
+        self.interrupted
+    }
+
+    pub fn success(&self) -> bool {
+        self.code == Some(0) && !self.timed_out && self.interrupted.is_none()
+    }
+
+    /// The exit code of the command, if it exited on its own.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// The signal that terminated the command, if any.
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    /// The code the CLI exits with: the one of the command or, as shells do, `128 + signal`.
+    /// A command that timed out exits with `124`, as with `timeout(1)`, and an interrupted one
+    /// with `128 + signal`, whatever the command did with the signal.
+    pub fn exit_code(&self) -> i32 {
+        if let Some(signal) = self.interrupted {
+            return 128 + signal;
+        }
+        if self.timed_out {
+            return 124;
+        }
+
+        self.code
+            .or(self.signal.map(|signal| 128 + signal))
+            .unwrap_or(1)
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+
+        Self::new(status.code(), signal)
+    }
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(signal) = self.interrupted {
+            return write!(f, "interrupted by signal {}", signal);
+        }
+
+        match (self.code, self.signal) {
+            _ if self.timed_out => write!(f, "timed out"),
+            (_, Some(signal)) => write!(f, "terminated by signal {}", signal),
+            (Some(code), _) => write!(f, "exited with code {}", code),
+            (None, None) => write!(f, "exited with an unknown status"),
+        }
+    }
+}
+
+/// A single run of a step, out of the ones allowed by its retry policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    step: String,
+    number: u32,
+    status: ExitStatus,
+    duration: Duration,
+}
+
+impl Attempt {
+    pub fn new(step: &str, number: u32, status: ExitStatus, duration: Duration) -> Self {
+        Self {
+            step: step.to_string(),
+            number,
+            status,
+            duration,
+        }
+    }
+
+    pub fn step(&self) -> &str {
+        &self.step
+    }
+
+    /// The number of the attempt, starting at `1`.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn status(&self) -> &ExitStatus {
+        &self.status
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl Attempt {
+    /// The attempt as a JSON document, for `--output json`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "step": self.step,
+            "number": self.number,
+            "status": self.status.to_string(),
+            "exit_code": self.status.exit_code(),
+            "duration": self.duration.as_secs_f64(),
+        })
+    }
+}
+
+impl Display for Attempt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "attempt {} {} after {:.1}s",
+            self.number,
+            self.status,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Output {
     r#type: String,
     message: String,
+    stdout: String,
+    stderr: String,
+    status: Option<ExitStatus>,
+    attempts: Vec<Attempt>,
+    data: Option<Value>,
 }
 
 impl Output {
@@ -9,9 +182,39 @@ impl Output {
         Self {
             r#type: r#type.to_string(),
             message: message.to_string(),
+            stdout: String::new(),
+            stderr: String::new(),
+            status: None,
+            attempts: Vec::new(),
+            data: None,
         }
     }
 
+    /// Attach the captured standard streams of an executed command.
+    pub fn with_streams(mut self, stdout: &str, stderr: &str) -> Self {
+        self.stdout = stdout.to_string();
+        self.stderr = stderr.to_string();
+        self
+    }
+
+    /// Attach the exit status of an executed command.
+    pub fn with_status(mut self, status: ExitStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Attach every attempt made to run the steps of a workflow.
+    pub fn with_attempts(mut self, attempts: Vec<Attempt>) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Attach the details of what was done, as an object whose keys end up in the JSON document.
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
     pub fn r#type(&self) -> &str {
         &self.r#type
     }
@@ -19,4 +222,160 @@ impl Output {
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    pub fn status(&self) -> Option<&ExitStatus> {
+        self.status.as_ref()
+    }
+
+    pub fn attempts(&self) -> &Vec<Attempt> {
+        &self.attempts
+    }
+
+    pub fn data(&self) -> Option<&Value> {
+        self.data.as_ref()
+    }
+
+    /// The code the CLI exits with, `0` unless a command was executed and failed.
+    pub fn exit_code(&self) -> i32 {
+        self.status.as_ref().map_or(0, ExitStatus::exit_code)
+    }
+
+    /// The output as a JSON document, for `--output json`. The outcome of an executed command
+    /// is only part of it when there is one, and `exit_source` tells whether the exit code is the
+    /// one of the command or of `workflow`, since a command may use the codes of `Error` too.
+    pub fn to_json(&self) -> Value {
+        let mut document = json!({
+            "type": self.r#type,
+            "message": self.message,
+            "exit_code": self.exit_code(),
+            "exit_source": match self.status {
+                Some(_) => "command",
+                None => "workflow",
+            },
+        });
+
+        if let Some(status) = &self.status {
+            document["status"] = json!(status.to_string());
+            document["stdout"] = json!(self.stdout);
+            document["stderr"] = json!(self.stderr);
+            document["attempts"] = self.attempts.iter().map(Attempt::to_json).collect();
+        }
+
+        // The keys above are the same for every output, so they win over the ones of the data
+        if let (Some(Value::Object(data)), Value::Object(fields)) = (&self.data, &mut document) {
+            for (key, value) in data {
+                fields.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+
+        document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_of_exited_command() {
+        let output = Output::new("command", "exit 3").with_status(ExitStatus::new(Some(3), None));
+
+        assert_eq!(output.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_exit_code_of_signaled_command() {
+        let status = ExitStatus::new(None, Some(9));
+
+        assert!(!status.success());
+        assert_eq!(status.exit_code(), 137);
+        assert_eq!(status.to_string(), "terminated by signal 9");
+    }
+
+    #[test]
+    fn test_exit_code_of_timed_out_command() {
+        let status = ExitStatus::new(None, Some(15)).into_timed_out();
+
+        assert!(!status.success());
+        assert_eq!(status.exit_code(), 124);
+        assert_eq!(
+            Attempt::new("build", 2, status, Duration::from_millis(1520)).to_string(),
+            "attempt 2 timed out after 1.5s"
+        );
+    }
+
+    #[test]
+    fn test_exit_code_of_interrupted_command() {
+        // The command handled the signal and exited on its own
+        let status = ExitStatus::new(Some(0), None).into_interrupted(2);
+
+        assert!(!status.success());
+        assert_eq!(status.exit_code(), 130);
+        assert_eq!(status.to_string(), "interrupted by signal 2");
+    }
+
+    #[test]
+    fn test_output_to_json() {
+        let output = Output::new("command", "exit 3")
+            .with_streams("out\n", "err\n")
+            .with_status(ExitStatus::new(Some(3), None))
+            .with_attempts(vec![Attempt::new(
+                "command",
+                1,
+                ExitStatus::new(Some(3), None),
+                Duration::from_millis(250),
+            )])
+            .with_data(json!({ "workflow": "fail", "exit_code": null }));
+
+        assert_eq!(
+            output.to_json(),
+            json!({
+                "type": "command",
+                "message": "exit 3",
+                "exit_code": 3,
+                "exit_source": "command",
+                "status": "exited with code 3",
+                "stdout": "out\n",
+                "stderr": "err\n",
+                "attempts": [{
+                    "step": "command",
+                    "number": 1,
+                    "status": "exited with code 3",
+                    "exit_code": 3,
+                    "duration": 0.25,
+                }],
+                "workflow": "fail",
+            })
+        );
+    }
+
+    #[test]
+    fn test_output_to_json_without_command() {
+        let output = Output::new("list", "success");
+
+        assert_eq!(
+            output.to_json(),
+            json!({
+                "type": "list",
+                "message": "success",
+                "exit_code": 0,
+                "exit_source": "workflow",
+            })
+        );
+    }
+
+    #[test]
+    fn test_exit_code_without_command() {
+        let output = Output::new("list", "success");
+
+        assert_eq!(output.exit_code(), 0);
+    }
 }
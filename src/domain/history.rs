@@ -0,0 +1,132 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::prelude::WorkflowId;
+
+/// A past run of a workflow, as recorded in the history.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// The id of the workflow that was run
+    workflow: WorkflowId,
+    /// The value of every argument of the workflow
+    arguments: BTreeMap<String, String>,
+    /// The rendered command, or commands of the steps one per line
+    command: String,
+    /// The directory the command was run in
+    cwd: Option<PathBuf>,
+    /// When the run started
+    started_at: DateTime<Utc>,
+    /// How long the run took
+    duration: Duration,
+    /// The code `workflow` exited with
+    exit_code: i32,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        workflow: WorkflowId,
+        arguments: &HashMap<String, String>,
+        command: &str,
+        cwd: Option<&Path>,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        exit_code: i32,
+    ) -> Self {
+        Self {
+            workflow,
+            arguments: arguments
+                .iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+            command: command.to_string(),
+            cwd: cwd.map(Path::to_path_buf),
+            started_at,
+            duration,
+            exit_code,
+        }
+    }
+
+    /// The key of the entry in the store, sorting entries by start time.
+    pub fn key(&self) -> String {
+        format!(
+            "{:020}-{}",
+            self.started_at.timestamp_nanos_opt().unwrap_or_default(),
+            self.workflow.inner()
+        )
+    }
+
+    pub fn workflow(&self) -> &WorkflowId {
+        &self.workflow
+    }
+
+    pub fn arguments(&self) -> &BTreeMap<String, String> {
+        &self.arguments
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// The entry as a JSON document, for `--output json`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "workflow": self.workflow.inner(),
+            "arguments": self.arguments,
+            "command": self.command,
+            "cwd": self.cwd,
+            "started_at": self.started_at,
+            "duration": self.duration.as_secs_f64(),
+            "exit_code": self.exit_code,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_keys_sort_by_start_time() {
+        let entry = |workflow: &str, seconds: i64| {
+            HistoryEntry::new(
+                WorkflowId::from(workflow),
+                &HashMap::new(),
+                "true",
+                None,
+                DateTime::from_timestamp(seconds, 0).unwrap(),
+                Duration::ZERO,
+                0,
+            )
+        };
+
+        assert!(entry("zeta", 9).key() < entry("alpha", 10).key());
+        assert!(entry("alpha", 10).key() < entry("alpha", 100).key());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::prelude::Workflow;
+
+/// A run of a workflow detached from the terminal, as recorded in the store.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Job {
+    /// The number of the job, counting from 1
+    id: u64,
+    /// The workflow that is run, kept whole so the detached process does not need the store
+    workflow: Workflow,
+    /// The value of every argument of the workflow
+    arguments: BTreeMap<String, String>,
+    /// The rendered command, or commands of the steps one per line
+    command: String,
+    /// The process running the workflow, known once it is started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    /// When the job started
+    started_at: DateTime<Utc>,
+}
+
+impl Job {
+    pub fn new(
+        id: u64,
+        workflow: Workflow,
+        arguments: &HashMap<String, String>,
+        command: &str,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id,
+            workflow,
+            arguments: arguments
+                .iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+            command: command.to_string(),
+            pid: None,
+            started_at,
+        }
+    }
+
+    pub fn with_pid(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// The key of the job in the store, sorting jobs by number.
+    pub fn key(&self) -> String {
+        job_key(self.id)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn workflow(&self) -> &Workflow {
+        &self.workflow
+    }
+
+    pub fn arguments(&self) -> &BTreeMap<String, String> {
+        &self.arguments
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// The job as a JSON document with its current status, for `--output json`.
+    pub fn to_json(&self, status: JobStatus) -> Value {
+        json!({
+            "job": self.id,
+            "workflow": self.workflow.id().inner(),
+            "arguments": self.arguments,
+            "command": self.command,
+            "pid": self.pid,
+            "started_at": self.started_at,
+            "status": status.to_string(),
+            "exit_code": match status {
+                JobStatus::Exited(code) => Some(code),
+                JobStatus::Running | JobStatus::Lost => None,
+            },
+        })
+    }
+}
+
+/// The key of the job with the given number in the store.
+pub fn job_key(id: u64) -> String {
+    format!("{:020}", id)
+}
+
+/// Where a detached job stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The workflow is still running
+    Running,
+    /// The workflow is done, `workflow` exited with the given code
+    Exited(i32),
+    /// The process is gone without recording how it ended, e.g. after a reboot
+    Lost,
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Exited(code) => write!(f, "exit {}", code),
+            JobStatus::Lost => write!(f, "lost"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_keys_sort_by_number() {
+        let job = |id: u64| {
+            Job::new(
+                id,
+                Workflow::slim("test", "true", Vec::new()),
+                &HashMap::new(),
+                "true",
+                Utc::now(),
+            )
+        };
+
+        assert!(job(9).key() < job(10).key());
+        assert_eq!(job(10).key(), job_key(10));
+    }
+}
@@ -3,7 +3,11 @@ pub mod command;
 pub mod error;
 pub mod executor;
 pub mod file;
+pub mod history;
 pub mod index;
+pub mod job;
+pub mod schedule;
+pub mod step;
 pub mod workflow;
 
 pub mod prelude {
@@ -18,7 +22,15 @@ pub mod prelude {
     #[doc(inline)]
     pub use super::file::*;
     #[doc(inline)]
+    pub use super::history::*;
+    #[doc(inline)]
     pub use super::index::*;
     #[doc(inline)]
+    pub use super::job::*;
+    #[doc(inline)]
+    pub use super::schedule::*;
+    #[doc(inline)]
+    pub use super::step::*;
+    #[doc(inline)]
     pub use super::workflow::*;
 }
@@ -0,0 +1,119 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::prelude::{WorkflowId, WorkflowSchedule};
+
+/// A run of a workflow started by `workflow scheduler`, as recorded in the store once it is done.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ScheduledRun {
+    /// The id of the workflow that was run
+    workflow: WorkflowId,
+    /// The schedule the run is due to
+    schedule: WorkflowSchedule,
+    /// The value of every argument of the workflow
+    arguments: BTreeMap<String, String>,
+    /// The job the workflow was run as
+    job: u64,
+    /// When the run started
+    started_at: DateTime<Utc>,
+    /// How long the run took
+    duration: Duration,
+    /// The code the run exited with
+    exit_code: i32,
+    /// The directory holding the output of the run, `stdout.log` and `stderr.log`
+    log: PathBuf,
+}
+
+impl ScheduledRun {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        workflow: WorkflowId,
+        schedule: WorkflowSchedule,
+        arguments: &HashMap<String, String>,
+        job: u64,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        exit_code: i32,
+        log: &Path,
+    ) -> Self {
+        Self {
+            workflow,
+            schedule,
+            arguments: arguments
+                .iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+            job,
+            started_at,
+            duration,
+            exit_code,
+            log: log.to_path_buf(),
+        }
+    }
+
+    /// The key of the run in the store, sorting runs by start time.
+    pub fn key(&self) -> String {
+        format!(
+            "{:020}-{}",
+            self.started_at.timestamp_nanos_opt().unwrap_or_default(),
+            self.job
+        )
+    }
+
+    pub fn workflow(&self) -> &WorkflowId {
+        &self.workflow
+    }
+
+    pub fn schedule(&self) -> &WorkflowSchedule {
+        &self.schedule
+    }
+
+    pub fn arguments(&self) -> &BTreeMap<String, String> {
+        &self.arguments
+    }
+
+    pub fn job(&self) -> u64 {
+        self.job
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn log(&self) -> &Path {
+        &self.log
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// The run as a JSON document, for `--output json`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "workflow": self.workflow.inner(),
+            "cron": self.schedule.cron().inner(),
+            "arguments": self.arguments,
+            "job": self.job,
+            "started_at": self.started_at,
+            "duration": self.duration.as_secs_f64(),
+            "exit_code": self.exit_code,
+            "log": self.log,
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::{
+    Argument, WorkflowCommand, WorkflowCondition, WorkflowDuration, WorkflowRetry,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+pub struct StepName(String);
+
+impl From<&str> for StepName {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl StepName {
+    pub fn inner(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A single command of a multi-step workflow.
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+pub struct Step {
+    /// The name of the step
+    name: StepName,
+    /// The command to run the step
+    command: WorkflowCommand,
+    /// The arguments only used by this step, on top of the ones of the workflow
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    arguments: Vec<Argument>,
+    /// Whether the next steps still run when this one fails
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    continue_on_error: bool,
+    /// The steps that have to finish before this one starts
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    needs: Vec<StepName>,
+    /// How long the step may run before it is killed, instead of the timeout of the workflow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<WorkflowDuration>,
+    /// How the step is retried when failing, instead of the retry policy of the workflow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry: Option<WorkflowRetry>,
+    /// When the step runs, it is skipped otherwise
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    condition: Option<WorkflowCondition>,
+}
+
+impl Step {
+    pub fn new(
+        name: &str,
+        command: &str,
+        arguments: Vec<Argument>,
+        continue_on_error: bool,
+    ) -> Self {
+        Self {
+            name: StepName::from(name),
+            command: WorkflowCommand::from(command),
+            arguments,
+            continue_on_error,
+            needs: Vec::new(),
+            timeout: None,
+            retry: None,
+            condition: None,
+        }
+    }
+
+    /// Rename the step.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = StepName::from(name);
+        self
+    }
+
+    /// Make the step wait for the given steps.
+    pub fn with_needs(mut self, needs: Vec<&str>) -> Self {
+        self.needs = needs.into_iter().map(StepName::from).collect();
+        self
+    }
+
+    pub fn name(&self) -> &StepName {
+        &self.name
+    }
+
+    pub fn command(&self) -> &WorkflowCommand {
+        &self.command
+    }
+
+    pub fn arguments(&self) -> &Vec<Argument> {
+        &self.arguments
+    }
+
+    pub fn continue_on_error(&self) -> bool {
+        self.continue_on_error
+    }
+
+    pub fn needs(&self) -> &Vec<StepName> {
+        &self.needs
+    }
+
+    pub fn timeout(&self) -> Option<WorkflowDuration> {
+        self.timeout
+    }
+
+    pub fn retry(&self) -> Option<WorkflowRetry> {
+        self.retry
+    }
+
+    pub fn condition(&self) -> Option<&WorkflowCondition> {
+        self.condition.as_ref()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+/// Find a cycle in the `needs` of the given steps, as the path of step names that closes it,
+/// e.g. `[a, b, a]`. Needs that do not match any step are ignored.
+pub fn find_cycle(steps: &[Step]) -> Option<Vec<&StepName>> {
+    fn visit<'a>(
+        index: usize,
+        steps: &'a [Step],
+        marks: &mut Vec<Mark>,
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<&'a StepName>> {
+        marks[index] = Mark::Visiting;
+        path.push(index);
+
+        for need in steps[index].needs() {
+            let Some(next) = steps.iter().position(|step| step.name() == need) else {
+                continue;
+            };
+
+            match marks[next] {
+                Mark::Visiting => {
+                    let start = path.iter().position(|&i| i == next).unwrap_or_default();
+                    let mut cycle = path[start..]
+                        .iter()
+                        .map(|&i| steps[i].name())
+                        .collect::<Vec<&StepName>>();
+                    cycle.push(steps[next].name());
+                    return Some(cycle);
+                }
+                Mark::Unvisited => {
+                    if let Some(cycle) = visit(next, steps, marks, path) {
+                        return Some(cycle);
+                    }
+                }
+                Mark::Visited => {}
+            }
+        }
+
+        path.pop();
+        marks[index] = Mark::Visited;
+        None
+    }
+
+    let mut marks = vec![Mark::Unvisited; steps.len()];
+    (0..steps.len()).find_map(|index| {
+        if marks[index] == Mark::Unvisited {
+            visit(index, steps, &mut marks, &mut Vec::new())
+        } else {
+            None
+        }
+    })
+}
+
+/// Sort the steps so every step comes after the ones it needs, keeping the declaration order
+/// otherwise. Steps in a cycle are kept at the end in declaration order.
+pub fn topological_order(steps: Vec<Step>) -> Vec<Step> {
+    let mut pending = steps;
+    let mut ordered: Vec<Step> = Vec::with_capacity(pending.len());
+
+    while let Some(index) = pending.iter().position(|step| {
+        step.needs().iter().all(|need| {
+            ordered.iter().any(|done| done.name() == need)
+                || !pending.iter().any(|other| other.name() == need)
+        })
+    }) {
+        ordered.push(pending.remove(index));
+    }
+
+    ordered.extend(pending);
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        let steps = vec![
+            Step::new("a", "a", Vec::new(), false).with_needs(vec!["c"]),
+            Step::new("b", "b", Vec::new(), false).with_needs(vec!["a"]),
+            Step::new("c", "c", Vec::new(), false).with_needs(vec!["b"]),
+        ];
+
+        let cycle = find_cycle(&steps)
+            .unwrap()
+            .into_iter()
+            .map(|name| name.inner())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(cycle, vec!["a", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_find_cycle_without_cycle() {
+        let steps = vec![
+            Step::new("a", "a", Vec::new(), false),
+            Step::new("b", "b", Vec::new(), false).with_needs(vec!["a"]),
+            Step::new("c", "c", Vec::new(), false).with_needs(vec!["a", "b"]),
+        ];
+
+        assert!(find_cycle(&steps).is_none());
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let steps = vec![
+            Step::new("deploy", "deploy", Vec::new(), false).with_needs(vec!["build", "test"]),
+            Step::new("test", "test", Vec::new(), false).with_needs(vec!["build"]),
+            Step::new("lint", "lint", Vec::new(), false),
+            Step::new("build", "build", Vec::new(), false),
+        ];
+
+        let names = topological_order(steps)
+            .iter()
+            .map(|step| step.name().inner().to_owned())
+            .collect::<Vec<String>>();
+
+        assert_eq!(names, vec!["lint", "build", "test", "deploy"]);
+    }
+}
@@ -1,16 +1,27 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::{Display, Formatter},
+    hash::Hash,
+    iter::Peekable,
+    num::{NonZeroU32, NonZeroUsize},
     ops::Deref,
-    str::FromStr,
+    path::{Path, PathBuf},
+    str::{Chars, FromStr},
+    time::Duration,
 };
 
-use crate::prelude::Hasher;
+use crate::prelude::{
+    find_cycle, home_dir, topological_order, Condition, Hasher, Scope, Step, Unit, SCRIPT_HELPERS,
+};
 
 use super::{args::Argument, prelude::Error};
+use chrono::{DateTime, Local};
+use croner::Cron;
 use handlebars::Handlebars;
 use inquire::CustomUserError;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use strsim::normalized_levenshtein;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
@@ -46,19 +57,947 @@ impl WorkflowDescription {
 #[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
 pub struct WorkflowCommand(String);
 
+impl From<&str> for WorkflowCommand {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
 impl WorkflowCommand {
     pub fn inner(&self) -> &str {
         &self.0
     }
 
-    pub fn replace(&self, arguments: &HashMap<String, String>) -> Result<String, Error> {
-        // Replace everything that is inside of {{}} with the value of the argument
-        let mut handlebars = Handlebars::new();
-        handlebars.register_escape_fn(|s| s.replace('\'', "\\'"));
+    /// Render the command with the value of the arguments, e.g. `{{name}}`, and the one of the
+    /// variables, e.g. `{{vars.sha}}`. Values are quoted for the given shell as needed to be
+    /// read as a single word, `{{quote name}}` always quotes the value and `{{raw name}}` inserts
+    /// it as is. The other helpers are the ones of `renderer`.
+    pub fn replace(
+        &self,
+        arguments: &HashMap<String, String>,
+        vars: &BTreeMap<String, String>,
+        shell: &WorkflowShell,
+    ) -> Result<String, Error> {
+        let mut context = arguments
+            .iter()
+            .map(|(key, value)| (key.to_owned(), Value::from(value.as_str())))
+            .collect::<Map<String, Value>>();
+        context.insert("vars".to_string(), json!(vars));
 
-        handlebars
-            .render_template(&self.0, arguments)
+        SCRIPT_HELPERS
+            .renderer(*shell)?
+            .render_template(&self.0, &context)
             .map_err(|e| Error::ParseError(Some(e.into())))
+            .and_then(|command| shell.escape_values(&command))
+    }
+}
+
+/// Render a template that is not run by a shell, so nothing is escaped.
+fn render(template: &str, arguments: &HashMap<String, String>) -> Result<String, Error> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+
+    handlebars
+        .render_template(template, arguments)
+        .map_err(|e| Error::ParseError(Some(e.into())))
+}
+
+/// The interpreter the command of a workflow is run with.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowShell {
+    #[default]
+    Sh,
+    Bash,
+    Zsh,
+    Fish,
+    Pwsh,
+    /// The command is split into words and executed directly, without any shell
+    Exec,
+}
+
+impl WorkflowShell {
+    /// The program that has to be installed to run the command, `None` for `exec`.
+    pub fn program(&self) -> Option<&str> {
+        match self {
+            WorkflowShell::Sh => Some("sh"),
+            WorkflowShell::Bash => Some("bash"),
+            WorkflowShell::Zsh => Some("zsh"),
+            WorkflowShell::Fish => Some("fish"),
+            WorkflowShell::Pwsh => Some("pwsh"),
+            WorkflowShell::Exec => None,
+        }
+    }
+
+    /// Quote the value so that the shell reads it as a single word, whatever it contains.
+    pub fn quote(&self, value: &str) -> String {
+        format!("'{}'", self.escape_within(Quoting::Single, value))
+    }
+
+    /// Quote the value only if the shell could read it otherwise than as it is, as a single word.
+    pub fn escape(&self, value: &str) -> String {
+        let is_plain = !value.is_empty()
+            && value
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || "_-./:+".contains(char));
+
+        match is_plain {
+            true => value.to_string(),
+            false => self.quote(value),
+        }
+    }
+
+    /// Mark a value inserted in a command, for `escape_values` to escape it once the whole
+    /// command is known. The markers are dropped from the value itself.
+    pub fn mark(value: &str) -> String {
+        let value = value.replace([VALUE_START, VALUE_END], "");
+        format!("{}{}{}", VALUE_START, value, VALUE_END)
+    }
+
+    /// Escape the values marked with `mark` in the command, as the quotes around each of them
+    /// require: a value within quotes, e.g. `"Hello {{name}}"`, is escaped for them rather than
+    /// quoted again, and a value outside of quotes is escaped with `escape`.
+    ///
+    /// # Returns
+    /// * The command or an `Error` if a value would end a here-document early
+    pub fn escape_values(&self, command: &str) -> Result<String, Error> {
+        let mut quotings = vec![Quoting::None];
+        // The here-documents whose delimiter is read, the first one being the current one
+        let mut heredocs = VecDeque::<HereDoc>::new();
+        let mut line_start = 0;
+        let mut escaped = String::with_capacity(command.len());
+        let mut chars = command.chars().peekable();
+
+        while let Some(char) = chars.next() {
+            let quoting = *quotings.last().unwrap_or(&Quoting::None);
+            if char == VALUE_START {
+                let value = chars
+                    .by_ref()
+                    .take_while(|char| *char != VALUE_END)
+                    .collect::<String>();
+                escaped.push_str(&self.escape_value(&quotings, &value));
+                continue;
+            }
+
+            let is_word_start = match escaped.chars().last() {
+                Some(last) => last.is_whitespace() || ";&|(".contains(last),
+                None => true,
+            };
+            escaped.push(char);
+            if char == '\n' {
+                match quoting {
+                    Quoting::HereDoc | Quoting::QuotedHereDoc => {
+                        check_heredoc_line(&escaped[line_start..], &heredocs)?;
+                    }
+                    // The body of a here-document starts on the line after its delimiter
+                    Quoting::None | Quoting::Substitution | Quoting::Backtick => {
+                        quotings.extend(heredocs.front().map(HereDoc::quoting));
+                    }
+                    _ => {}
+                }
+                // A line of the body that is the delimiter ends the here-document
+                while let Some(heredoc) = heredocs
+                    .front()
+                    .filter(|_| matches!(quotings.last(), Some(quoting) if quoting.is_heredoc()))
+                {
+                    let line = chars
+                        .clone()
+                        .take_while(|char| *char != '\n')
+                        .collect::<String>();
+                    if !heredoc.is_end(&line) {
+                        break;
+                    }
+                    escaped.push_str(&line);
+                    chars.nth(line.chars().count().saturating_sub(1));
+                    quotings.pop();
+                    heredocs.pop_front();
+                    if let Some(char) = chars.next() {
+                        escaped.push(char);
+                        quotings.extend(heredocs.front().map(HereDoc::quoting));
+                    }
+                }
+                line_start = escaped.len();
+                continue;
+            }
+
+            let next = chars.peek().copied().filter(|next| *next != VALUE_START);
+            let is_posix = self.is_posix();
+            let backtick = quotings
+                .iter()
+                .rposition(|quoting| *quoting == Quoting::Backtick);
+            let is_within_double_backticks =
+                backtick.is_some_and(|index| index > 0 && quotings[index - 1] == Quoting::Double);
+            match quoting {
+                // Within backticks within double quotes, `\"` is a double quote of the command
+                // within them
+                Quoting::Double
+                    if char == '\\' && next == Some('"') && is_within_double_backticks =>
+                {
+                    escaped.extend(chars.next());
+                    quotings.pop();
+                }
+                Quoting::None | Quoting::Substitution | Quoting::Backtick
+                    if char == '\\' && next == Some('"') && is_within_double_backticks =>
+                {
+                    escaped.extend(chars.next());
+                    quotings.push(Quoting::Double);
+                }
+                // Backticks end at the first one that is not escaped, whatever the quotes within
+                _ if char == '\\' && backtick.is_some() => {
+                    escaped.extend(next.and_then(|_| chars.next()));
+                }
+                _ if char == '`' && backtick.is_some() => {
+                    quotings.truncate(backtick.unwrap_or_default());
+                }
+                Quoting::Single if self.is_single_quote(char) => {
+                    quotings.pop();
+                }
+                // Fish reads `\'` and `\\` within single quotes
+                Quoting::Single if char == '\\' && *self == WorkflowShell::Fish => {
+                    escaped.extend(next.and_then(|_| chars.next()));
+                }
+                Quoting::Single | Quoting::QuotedHereDoc => {}
+                _ if char == self.escape_char() => {
+                    escaped.extend(next.and_then(|_| chars.next()));
+                }
+                Quoting::AnsiC if char == '\'' => {
+                    quotings.pop();
+                }
+                Quoting::AnsiC => {}
+                Quoting::Double if self.is_double_quote(char) => {
+                    quotings.pop();
+                }
+                Quoting::Double | Quoting::HereDoc if char == '$' && next == Some('(') => {
+                    escaped.extend(chars.next());
+                    quotings.push(Quoting::Substitution);
+                }
+                Quoting::Double | Quoting::HereDoc if char == '`' && is_posix => {
+                    quotings.push(Quoting::Backtick);
+                }
+                Quoting::Double | Quoting::HereDoc => {}
+                _ if char == '$' && next == Some('\'') && is_posix => {
+                    escaped.extend(chars.next());
+                    quotings.push(Quoting::AnsiC);
+                }
+                _ if self.is_single_quote(char) => quotings.push(Quoting::Single),
+                _ if self.is_double_quote(char) => quotings.push(Quoting::Double),
+                _ if char == '`' && is_posix => quotings.push(Quoting::Backtick),
+                _ if char == '<' && next == Some('<') && is_posix => {
+                    escaped.extend(chars.next());
+                    heredocs.extend(read_heredoc(&mut chars, &mut escaped)?);
+                }
+                // A quote within a comment, e.g. `# don't`, quotes nothing, and a value within it
+                // is kept within it
+                _ if char == '#' && is_word_start => {
+                    while let Some(next) =
+                        chars.next_if(|next| *next != '\n' && !(backtick.is_some() && *next == '`'))
+                    {
+                        match next {
+                            VALUE_START => {
+                                let value = chars
+                                    .by_ref()
+                                    .take_while(|char| *char != VALUE_END)
+                                    .collect::<String>();
+                                escaped.push_str(&value.replace('\n', "\n# "));
+                            }
+                            next => escaped.push(next),
+                        }
+                    }
+                }
+                Quoting::Substitution if char == '(' => quotings.push(Quoting::Substitution),
+                Quoting::Substitution if char == ')' => {
+                    quotings.pop();
+                }
+                Quoting::None | Quoting::Substitution | Quoting::Backtick => {}
+            }
+        }
+        if quotings.last().is_some_and(Quoting::is_heredoc) {
+            check_heredoc_line(&escaped[line_start..], &heredocs)?;
+        }
+
+        Ok(escaped)
+    }
+
+    /// Escape the value for the quotes it is within, the last ones being the innermost.
+    fn escape_value(&self, quotings: &[Quoting], value: &str) -> String {
+        let value = match quotings.last().copied().unwrap_or(Quoting::None) {
+            Quoting::None | Quoting::Substitution | Quoting::Backtick => self.escape(value),
+            quoting => self.escape_within(quoting, value),
+        };
+
+        // The backslashes before `\`, `` ` `` and `$` within backticks, and before `"` when they
+        // are within double quotes, are removed before the command within them is read
+        quotings
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, quoting)| **quoting == Quoting::Backtick)
+            .fold(value, |value, (index, _)| {
+                let is_within_double = index > 0 && quotings[index - 1] == Quoting::Double;
+                value
+                    .chars()
+                    .map(|char| match char {
+                        '\\' | '`' | '$' => format!("\\{}", char),
+                        '"' if is_within_double => format!("\\{}", char),
+                        char => char.to_string(),
+                    })
+                    .collect()
+            })
+    }
+
+    /// Escape the value for the shell to read it as it is within the given quotes.
+    fn escape_within(&self, quoting: Quoting, value: &str) -> String {
+        value
+            .chars()
+            .map(|char| match (quoting, self) {
+                (Quoting::Single, WorkflowShell::Fish) if char == '\'' || char == '\\' => {
+                    format!("\\{}", char)
+                }
+                (Quoting::Single, WorkflowShell::Fish) => char.to_string(),
+                // PowerShell also reads the typographic quotes as quotes
+                (Quoting::Single, WorkflowShell::Pwsh) if self.is_single_quote(char) => {
+                    format!("{}{}", char, char)
+                }
+                // Nothing is special within single quotes, so a quote ends them, is escaped and
+                // starts them again
+                (Quoting::Single, _) if char == '\'' => r"'\''".to_string(),
+                (Quoting::Double, WorkflowShell::Pwsh)
+                    if self.is_double_quote(char) || "`$".contains(char) =>
+                {
+                    format!("`{}", char)
+                }
+                (Quoting::Double, WorkflowShell::Fish) if "\\\"$".contains(char) => {
+                    format!("\\{}", char)
+                }
+                (Quoting::Double, WorkflowShell::Pwsh | WorkflowShell::Fish) => char.to_string(),
+                (Quoting::Double, _) if "\\\"$`".contains(char) => format!("\\{}", char),
+                // The quote ends the ANSI-C quotes, is escaped and starts them again, which is
+                // also safe for a shell reading `$'` as `$` and single quotes
+                (Quoting::AnsiC, _) if char == '\'' => r"'\'$'".to_string(),
+                (Quoting::AnsiC, _) if char == '\\' => r"\\".to_string(),
+                (Quoting::HereDoc, _) if "\\$`".contains(char) => format!("\\{}", char),
+                _ => char.to_string(),
+            })
+            .collect()
+    }
+
+    /// Whether the shell reads ANSI-C quotes, backticks and here-documents as `sh` does.
+    fn is_posix(&self) -> bool {
+        matches!(
+            self,
+            WorkflowShell::Sh | WorkflowShell::Bash | WorkflowShell::Zsh
+        )
+    }
+
+    fn escape_char(&self) -> char {
+        match self {
+            WorkflowShell::Pwsh => '`',
+            _ => '\\',
+        }
+    }
+
+    fn is_single_quote(&self, char: char) -> bool {
+        match self {
+            WorkflowShell::Pwsh => matches!(
+                char,
+                '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}'
+            ),
+            _ => char == '\'',
+        }
+    }
+
+    fn is_double_quote(&self, char: char) -> bool {
+        match self {
+            WorkflowShell::Pwsh => matches!(char, '"' | '\u{201C}' | '\u{201D}' | '\u{201E}'),
+            _ => char == '"',
+        }
+    }
+}
+
+/// The characters around a value of a command until it is escaped, from the private use area
+/// of Unicode so that no command uses them.
+const VALUE_START: char = '\u{E000}';
+const VALUE_END: char = '\u{E001}';
+
+/// The quotes a part of a command is within.
+#[derive(Clone, Copy, PartialEq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+    /// ANSI-C quotes, e.g. `$'a\tb'`, reading the escape sequences within them
+    AnsiC,
+    /// A command substitution within double quotes, e.g. `"$(date)"`, which starts anew
+    Substitution,
+    /// A command substitution with backticks, e.g. `` `date` ``
+    Backtick,
+    /// The body of a here-document, e.g. `<<EOF`, read as within double quotes
+    HereDoc,
+    /// The body of a here-document with a quoted delimiter, e.g. `<<'EOF'`, read as it is
+    QuotedHereDoc,
+}
+
+impl Quoting {
+    fn is_heredoc(&self) -> bool {
+        matches!(self, Quoting::HereDoc | Quoting::QuotedHereDoc)
+    }
+}
+
+/// A here-document of a command, e.g. `<<-EOF`, whose body starts on the next line.
+struct HereDoc {
+    delimiter: String,
+    /// Whether the tabs at the start of the lines are removed, with `<<-`
+    strip_tabs: bool,
+    is_quoted: bool,
+}
+
+impl HereDoc {
+    fn quoting(&self) -> Quoting {
+        match self.is_quoted {
+            true => Quoting::QuotedHereDoc,
+            false => Quoting::HereDoc,
+        }
+    }
+
+    /// Whether the line of the body is the delimiter, ending the here-document.
+    fn is_end(&self, line: &str) -> bool {
+        let line = match self.strip_tabs {
+            true => line.trim_start_matches('\t'),
+            false => line,
+        };
+
+        line == self.delimiter
+    }
+}
+
+/// Read the delimiter of a here-document after `<<`, copying it to the command as it is. A
+/// here-string, e.g. `<<<`, is none.
+///
+/// # Returns
+/// * The here-document or an `Error` if its delimiter is a value, which cannot be told apart
+fn read_heredoc(
+    chars: &mut Peekable<Chars>,
+    escaped: &mut String,
+) -> Result<Option<HereDoc>, Error> {
+    if let Some(char) = chars.next_if_eq(&'<') {
+        escaped.push(char);
+        return Ok(None);
+    }
+    let strip_tabs = chars
+        .next_if_eq(&'-')
+        .inspect(|char| escaped.push(*char))
+        .is_some();
+    while let Some(char) = chars.next_if(|char| *char == ' ' || *char == '\t') {
+        escaped.push(char);
+    }
+
+    let mut delimiter = String::new();
+    let mut is_quoted = false;
+    while let Some(char) = chars
+        .next_if(|char| !char.is_whitespace() && !";&|<>()".contains(*char) && *char != VALUE_START)
+    {
+        escaped.push(char);
+        match char {
+            '\'' | '"' => {
+                is_quoted = true;
+                while let Some(quoted) = chars.next_if(|quoted| *quoted != char) {
+                    escaped.push(quoted);
+                    delimiter.push(quoted);
+                }
+                escaped.extend(chars.next());
+            }
+            '\\' => {
+                is_quoted = true;
+                if let Some(escaped_char) = chars.next() {
+                    escaped.push(escaped_char);
+                    delimiter.push(escaped_char);
+                }
+            }
+            char => delimiter.push(char),
+        }
+    }
+    if chars.peek() == Some(&VALUE_START) {
+        return Err(Error::ParseError(Some(
+            "The delimiter of a here-document cannot have a value".into(),
+        )));
+    }
+
+    Ok((!delimiter.is_empty()).then_some(HereDoc {
+        delimiter,
+        strip_tabs,
+        is_quoted,
+    }))
+}
+
+/// Check that the values within the line of the body of the current here-document do not make
+/// a line of it the delimiter, which would run the rest of the body as commands.
+fn check_heredoc_line(line: &str, heredocs: &VecDeque<HereDoc>) -> Result<Unit, Error> {
+    let heredoc = match heredocs.front() {
+        Some(heredoc) => heredoc,
+        None => return Ok(()),
+    };
+
+    match line
+        .trim_end_matches('\n')
+        .split('\n')
+        .any(|line| heredoc.is_end(line))
+    {
+        true => Err(Error::ParseError(Some(
+            format!(
+                "A value would end the here-document `{}` early",
+                heredoc.delimiter
+            )
+            .into(),
+        ))),
+        false => Ok(()),
+    }
+}
+
+impl FromStr for WorkflowShell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sh" => Ok(WorkflowShell::Sh),
+            "bash" => Ok(WorkflowShell::Bash),
+            "zsh" => Ok(WorkflowShell::Zsh),
+            "fish" => Ok(WorkflowShell::Fish),
+            "pwsh" => Ok(WorkflowShell::Pwsh),
+            "exec" => Ok(WorkflowShell::Exec),
+            _ => Err(Error::ParseError(Some(
+                format!("Unknown shell `{}`", s).into(),
+            ))),
+        }
+    }
+}
+
+impl Display for WorkflowShell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program().unwrap_or("exec"))
+    }
+}
+
+/// A duration written as a number of seconds or with a unit, e.g. `500ms`, `30s`, `5m` or `1h`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+#[serde(try_from = "DurationValue", into = "String")]
+pub struct WorkflowDuration(Duration);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(u64),
+    Text(String),
+}
+
+impl WorkflowDuration {
+    pub fn new(duration: Duration) -> Self {
+        Self(duration)
+    }
+
+    pub fn inner(&self) -> Duration {
+        self.0
+    }
+}
+
+impl FromStr for WorkflowDuration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let invalid = || Error::ParseError(Some(format!("Invalid duration `{}`", s).into()));
+
+        let value = value.parse::<f64>().map_err(|_| invalid())?;
+        let seconds = match unit.trim() {
+            "ms" => value / 1000.0,
+            "" | "s" => value,
+            "m" => value * 60.0,
+            "h" => value * 3600.0,
+            _ => return Err(invalid()),
+        };
+
+        Duration::try_from_secs_f64(seconds)
+            .map(Self)
+            .map_err(|_| invalid())
+    }
+}
+
+impl TryFrom<DurationValue> for WorkflowDuration {
+    type Error = Error;
+
+    fn try_from(value: DurationValue) -> Result<Self, Self::Error> {
+        match value {
+            DurationValue::Seconds(seconds) => Ok(Self(Duration::from_secs(seconds))),
+            DurationValue::Text(text) => Self::from_str(&text),
+        }
+    }
+}
+
+impl From<WorkflowDuration> for String {
+    fn from(value: WorkflowDuration) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for WorkflowDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let millis = self.0.as_millis();
+        match millis {
+            millis if millis % 1000 != 0 => write!(f, "{}ms", millis),
+            millis if millis > 0 && millis % 3_600_000 == 0 => write!(f, "{}h", millis / 3_600_000),
+            millis if millis > 0 && millis % 60_000 == 0 => write!(f, "{}m", millis / 60_000),
+            millis => write!(f, "{}s", millis / 1000),
+        }
+    }
+}
+
+/// How the delay between two attempts grows.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowBackoff {
+    /// The same delay before every attempt
+    #[default]
+    Fixed,
+    /// The delay times the number of failed attempts
+    Linear,
+    /// The delay doubled after every failed attempt
+    Exponential,
+}
+
+/// When the command of a workflow is confirmed before it is executed.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowConfirm {
+    /// Every run is confirmed
+    #[default]
+    Always,
+    /// No run is confirmed, unless the command looks dangerous
+    Never,
+    /// Only the runs of a command that looks dangerous are confirmed
+    Dangerous,
+}
+
+/// Which environment variables of the shell `workflow` runs in the command inherits.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowEnvPolicy {
+    /// Every variable is inherited
+    #[default]
+    Inherit,
+    /// Only the variables needed to run anything, like `PATH` and `HOME`, are inherited
+    Clean,
+    /// The variables of `clean` and the ones of `env_allow` are inherited
+    Allowlist,
+}
+
+/// The variables inherited even by a clean environment, without which most commands fail.
+const ESSENTIAL_ENV: [&str; 17] = [
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "LANG",
+    "LC_ALL",
+    "TMPDIR",
+    "TZ",
+    "SYSTEMROOT",
+    "COMSPEC",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "APPDATA",
+];
+
+impl WorkflowEnvPolicy {
+    /// Whether the variable with the given name is inherited. A name of `allow` ending with `*`,
+    /// e.g. `AWS_*`, allows every variable starting with the rest of it.
+    pub fn allows(&self, name: &str, allow: &[String]) -> bool {
+        // The names of the variables are case-insensitive on Windows
+        let is_same = |expected: &str, name: &str| match cfg!(windows) {
+            true => expected.eq_ignore_ascii_case(name),
+            false => expected == name,
+        };
+        let is_essential = ESSENTIAL_ENV
+            .iter()
+            .any(|essential| is_same(essential, name));
+
+        match self {
+            WorkflowEnvPolicy::Inherit => true,
+            WorkflowEnvPolicy::Clean => is_essential,
+            WorkflowEnvPolicy::Allowlist => {
+                is_essential
+                    || allow.iter().any(|allowed| match allowed.strip_suffix('*') {
+                        Some(prefix) => name
+                            .get(..prefix.len())
+                            .is_some_and(|start| is_same(prefix, start)),
+                        None => is_same(allowed, name),
+                    })
+            }
+        }
+    }
+}
+
+/// An expression deciding whether a workflow or a step runs, e.g. `args.target == 'production'`,
+/// see `Condition`.
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct WorkflowCondition(String);
+
+impl WorkflowCondition {
+    pub fn inner(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the expression holds in the given scope.
+    pub fn evaluate(&self, scope: &Scope) -> Result<bool, Error> {
+        Ok(Condition::parse(&self.0)?.evaluate(scope))
+    }
+}
+
+impl FromStr for WorkflowCondition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Condition::parse(s)?;
+
+        Ok(Self(s.trim().to_string()))
+    }
+}
+
+impl TryFrom<String> for WorkflowCondition {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
+}
+
+impl From<WorkflowCondition> for String {
+    fn from(value: WorkflowCondition) -> Self {
+        value.0
+    }
+}
+
+impl Display for WorkflowCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A cron expression with five fields, e.g. `0 2 * * *` for every night at 2am, in local time.
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct WorkflowCron(String);
+
+impl WorkflowCron {
+    pub fn inner(&self) -> &str {
+        &self.0
+    }
+
+    /// The first time strictly after `time` the expression matches, if there is any.
+    pub fn next_after(&self, time: &DateTime<Local>) -> Option<DateTime<Local>> {
+        Cron::new(&self.0)
+            .parse()
+            .and_then(|cron| cron.find_next_occurrence(time, false))
+            .ok()
+    }
+}
+
+impl FromStr for WorkflowCron {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Cron::new(s).parse().map_err(|e| {
+            Error::ParseError(Some(
+                format!("Invalid cron expression `{}`: {}", s, e).into(),
+            ))
+        })?;
+
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for WorkflowCron {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
+}
+
+impl From<WorkflowCron> for String {
+    fn from(value: WorkflowCron) -> Self {
+        value.0
+    }
+}
+
+impl Display for WorkflowCron {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A value computed from the output of a command before the workflow is rendered, used as
+/// `{{vars.<name>}}` in its commands.
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+pub struct WorkflowVar {
+    /// The name of the variable
+    name: String,
+    /// The command printing the value, which can use the arguments and the variables before it
+    command: WorkflowCommand,
+    /// Whether the whitespace around the output is removed, unless `regex` is set
+    #[serde(default = "default_trim", skip_serializing_if = "Clone::clone")]
+    trim: bool,
+    /// Extracts the value from the output: its first capture group, or the whole match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regex: Option<WorkflowRegex>,
+}
+
+fn default_trim() -> bool {
+    true
+}
+
+impl WorkflowVar {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn command(&self) -> &WorkflowCommand {
+        &self.command
+    }
+
+    /// The value of the variable, given the output of its command.
+    ///
+    /// # Returns
+    /// * The value or an `Error` if the regex does not match the output
+    pub fn extract(&self, output: &str) -> Result<String, Error> {
+        let Some(regex) = &self.regex else {
+            return Ok(match self.trim {
+                true => output.trim().to_string(),
+                false => output.to_string(),
+            });
+        };
+
+        regex
+            .inner()
+            .captures(output)
+            .and_then(|captures| captures.get(1).or(captures.get(0)))
+            .map(|value| value.as_str().to_string())
+            .ok_or(Error::InvalidCommand(Some(
+                format!(
+                    "The output of variable `{}` does not match `{}`",
+                    self.name,
+                    regex.inner().as_str()
+                )
+                .into(),
+            )))
+    }
+}
+
+/// A regular expression, checked when the workflow is read.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct WorkflowRegex(Regex);
+
+impl WorkflowRegex {
+    pub fn inner(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for WorkflowRegex {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Regex::new(&value).map(Self).map_err(|e| {
+            Error::ParseError(Some(format!("Invalid regex `{}`: {}", value, e).into()))
+        })
+    }
+}
+
+impl From<WorkflowRegex> for String {
+    fn from(value: WorkflowRegex) -> Self {
+        value.0.as_str().to_string()
+    }
+}
+
+impl PartialEq for WorkflowRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for WorkflowRegex {}
+
+impl Hash for WorkflowRegex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state);
+    }
+}
+
+/// When a workflow runs on its own with `workflow scheduler`, and with which argument values.
+#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+pub struct WorkflowSchedule {
+    /// When to run the workflow
+    cron: WorkflowCron,
+    /// The value of the arguments, the other ones fall back to their default value
+    #[serde(default = "BTreeMap::new", skip_serializing_if = "BTreeMap::is_empty")]
+    arguments: BTreeMap<String, String>,
+}
+
+impl WorkflowSchedule {
+    pub fn cron(&self) -> &WorkflowCron {
+        &self.cron
+    }
+
+    pub fn arguments(&self) -> &BTreeMap<String, String> {
+        &self.arguments
+    }
+}
+
+/// How many times a failing command is run and how long to wait in between.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct WorkflowRetry {
+    /// The maximum number of attempts, the first one included
+    attempts: NonZeroU32,
+    /// The delay before the second attempt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delay: Option<WorkflowDuration>,
+    /// How the delay grows for the next attempts
+    #[serde(default)]
+    backoff: WorkflowBackoff,
+}
+
+impl WorkflowRetry {
+    pub fn new(attempts: NonZeroU32, delay: Duration, backoff: WorkflowBackoff) -> Self {
+        Self {
+            attempts,
+            delay: Some(WorkflowDuration(delay)),
+            backoff,
+        }
+    }
+
+    pub fn attempts(&self) -> NonZeroU32 {
+        self.attempts
+    }
+
+    /// The delay to wait after the given failed attempt, starting at `1`.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let delay = self.delay.map(|delay| delay.inner()).unwrap_or_default();
+        match self.backoff {
+            WorkflowBackoff::Fixed => delay,
+            WorkflowBackoff::Linear => delay.saturating_mul(attempt),
+            WorkflowBackoff::Exponential => {
+                delay.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            }
+        }
     }
 }
 
@@ -166,6 +1105,13 @@ impl From<String> for WorkflowTag {
 #[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
 pub struct WorkflowId(String);
 
+/// The id of the workflow with the given name.
+impl From<&str> for WorkflowId {
+    fn from(name: &str) -> Self {
+        Self(name.trim().to_lowercase().replace(['-', ' '], "_"))
+    }
+}
+
 impl WorkflowId {
     pub fn inner(&self) -> &str {
         &self.0
@@ -180,7 +1126,65 @@ pub struct Workflow {
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<WorkflowDescription>,
     /// The command to run the workflow
-    command: WorkflowCommand,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<WorkflowCommand>,
+    /// The commands to run in sequence, instead of a single command
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<Step>,
+    /// The shell to run the command with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shell: Option<WorkflowShell>,
+    /// The maximum number of steps running at the same time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    concurrency: Option<NonZeroUsize>,
+    /// How long the command may run before it is killed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<WorkflowDuration>,
+    /// How a failing command is retried
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry: Option<WorkflowRetry>,
+    /// The environment variables to run the command with, on top of the inherited ones
+    #[serde(default = "BTreeMap::new", skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    /// Which environment variables are inherited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env_policy: Option<WorkflowEnvPolicy>,
+    /// The environment variables inherited with the `allowlist` policy
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    env_allow: Vec<String>,
+    /// The directory to run the command in, relative to the workflow file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    /// When the command is confirmed before it is executed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirm: Option<WorkflowConfirm>,
+    /// The globs of the files that run the workflow again when they change, relative to `cwd`
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    watch: Vec<String>,
+    /// When the workflow runs on its own with `workflow scheduler`
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    schedule: Vec<WorkflowSchedule>,
+    /// The values computed from the output of commands, in order
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    vars: Vec<WorkflowVar>,
+    /// When the workflow runs, it is skipped otherwise
+    #[serde(rename = "if", skip_serializing_if = "Option::is_none")]
+    condition: Option<WorkflowCondition>,
+    /// The values of arguments to run the workflow with, once per combination
+    #[serde(default = "BTreeMap::new", skip_serializing_if = "BTreeMap::is_empty")]
+    matrix: BTreeMap<String, Vec<String>>,
+    /// The commands to run, in order, before the command of the workflow
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    before: Vec<WorkflowCommand>,
+    /// The commands to run, in order, after the command of the workflow, whatever its outcome
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    after: Vec<WorkflowCommand>,
+    /// The commands to run, in order, when the command of the workflow fails
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    on_failure: Vec<WorkflowCommand>,
+    /// The file the workflow was read from, set when crawling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
     /// The commands to run the workflow
     #[serde(default = "Vec::new")]
     arguments: Vec<Argument>,
@@ -198,6 +1202,7 @@ pub struct Workflow {
     tags: Vec<WorkflowTag>,
 }
 
+#[allow(clippy::to_string_trait_impl)]
 impl ToString for Workflow {
     fn to_string(&self) -> String {
         format!("{:#?}", self)
@@ -218,7 +1223,26 @@ impl Workflow {
             name: WorkflowName(name.to_string()),
             description: description
                 .map(|description| WorkflowDescription(description.to_string())),
-            command: WorkflowCommand(command.to_string()),
+            command: Some(WorkflowCommand(command.to_string())),
+            steps: Vec::new(),
+            shell: None,
+            concurrency: None,
+            timeout: None,
+            retry: None,
+            env: BTreeMap::new(),
+            env_policy: None,
+            env_allow: Vec::new(),
+            cwd: None,
+            confirm: None,
+            watch: Vec::new(),
+            schedule: Vec::new(),
+            vars: Vec::new(),
+            condition: None,
+            matrix: BTreeMap::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            on_failure: Vec::new(),
+            path: None,
             arguments,
             source: source.map(|source| WorkflowSource(source.to_string())),
             author: author.map(|author| WorkflowAuthor(author.to_string())),
@@ -232,7 +1256,26 @@ impl Workflow {
         Self {
             name: WorkflowName(name.to_string()),
             description: None,
-            command: WorkflowCommand(command.to_string()),
+            command: Some(WorkflowCommand(command.to_string())),
+            steps: Vec::new(),
+            shell: None,
+            concurrency: None,
+            timeout: None,
+            retry: None,
+            env: BTreeMap::new(),
+            env_policy: None,
+            env_allow: Vec::new(),
+            cwd: None,
+            confirm: None,
+            watch: Vec::new(),
+            schedule: Vec::new(),
+            vars: Vec::new(),
+            condition: None,
+            matrix: BTreeMap::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            on_failure: Vec::new(),
+            path: None,
             arguments,
             source: None,
             author: None,
@@ -242,13 +1285,7 @@ impl Workflow {
     }
 
     pub fn id(&self) -> WorkflowId {
-        WorkflowId(
-            self.name
-                .inner()
-                .trim()
-                .to_lowercase()
-                .replace(['-', ' '], "_"),
-        )
+        WorkflowId::from(self.name.inner())
     }
 
     pub fn name(&self) -> &WorkflowName {
@@ -263,8 +1300,221 @@ impl Workflow {
         self.description.as_ref()
     }
 
-    pub fn command(&self) -> &WorkflowCommand {
-        &self.command
+    pub fn command(&self) -> Option<&WorkflowCommand> {
+        self.command.as_ref()
+    }
+
+    /// The steps of the workflow, each one after the steps it needs. A workflow with a single
+    /// command is a single step named after the workflow.
+    pub fn steps(&self) -> Vec<Step> {
+        match &self.command {
+            Some(command) => vec![Step::new(
+                self.name.inner(),
+                command.inner(),
+                Vec::new(),
+                false,
+            )],
+            None => topological_order(self.steps.clone()),
+        }
+    }
+
+    /// Whether the workflow declares a list of steps rather than a single command.
+    pub fn is_multi_step(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// The arguments of the workflow followed by the ones only declared by its steps.
+    pub fn all_arguments(&self) -> Vec<&Argument> {
+        self.steps.iter().flat_map(|step| step.arguments()).fold(
+            self.arguments.iter().collect(),
+            |mut acc, argument| {
+                if !acc.iter().any(|known| known.name() == argument.name()) {
+                    acc.push(argument);
+                }
+                acc
+            },
+        )
+    }
+
+    /// Check that the workflow has either a command or steps, that step names are unique, that
+    /// the `needs` of the steps refer to existing steps without any cycle and that every
+    /// argument of the matrix has values.
+    pub fn validate(&self) -> Result<Unit, Error> {
+        let invalid = |message: String| Err(Error::InvalidCommand(Some(message.into())));
+        let name = self.name.inner();
+
+        match (&self.command, self.steps.is_empty()) {
+            (Some(_), false) => {
+                return invalid(format!(
+                    "The workflow `{}` has both a command and steps",
+                    name
+                ))
+            }
+            (None, true) => {
+                return invalid(format!(
+                    "The workflow `{}` has neither a command nor steps",
+                    name
+                ))
+            }
+            _ => {}
+        }
+
+        let duplicate = self.steps.iter().enumerate().find(|(index, step)| {
+            self.steps[..*index]
+                .iter()
+                .any(|previous| previous.name() == step.name())
+        });
+
+        if let Some((_, step)) = duplicate {
+            return invalid(format!(
+                "The step `{}` of workflow `{}` is declared more than once",
+                step.name().inner(),
+                name
+            ));
+        }
+
+        let unknown = self.steps.iter().find_map(|step| {
+            step.needs()
+                .iter()
+                .find(|need| !self.steps.iter().any(|other| other.name() == *need))
+                .map(|need| (step, need))
+        });
+
+        if let Some((step, need)) = unknown {
+            return invalid(format!(
+                "The step `{}` of workflow `{}` needs the unknown step `{}`",
+                step.name().inner(),
+                name,
+                need.inner()
+            ));
+        }
+
+        if let Some((argument, _)) = self.matrix.iter().find(|(_, values)| values.is_empty()) {
+            return invalid(format!(
+                "The argument `{}` of the matrix of workflow `{}` has no values",
+                argument, name
+            ));
+        }
+
+        if let Some(cycle) = find_cycle(&self.steps) {
+            let path = cycle
+                .iter()
+                .map(|step| step.inner())
+                .collect::<Vec<&str>>()
+                .join(" -> ");
+
+            return invalid(format!(
+                "The steps of workflow `{}` need each other in a cycle: {}",
+                name, path
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn concurrency(&self) -> Option<NonZeroUsize> {
+        self.concurrency
+    }
+
+    pub fn timeout(&self) -> Option<WorkflowDuration> {
+        self.timeout
+    }
+
+    pub fn retry(&self) -> Option<WorkflowRetry> {
+        self.retry
+    }
+
+    pub fn confirm(&self) -> WorkflowConfirm {
+        self.confirm.unwrap_or_default()
+    }
+
+    pub fn schedule(&self) -> &[WorkflowSchedule] {
+        &self.schedule
+    }
+
+    pub fn vars(&self) -> &[WorkflowVar] {
+        &self.vars
+    }
+
+    pub fn condition(&self) -> Option<&WorkflowCondition> {
+        self.condition.as_ref()
+    }
+
+    pub fn matrix(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.matrix
+    }
+
+    pub fn before(&self) -> &[WorkflowCommand] {
+        &self.before
+    }
+
+    pub fn after(&self) -> &[WorkflowCommand] {
+        &self.after
+    }
+
+    pub fn on_failure(&self) -> &[WorkflowCommand] {
+        &self.on_failure
+    }
+
+    /// The globs of the files to watch, rendered with the given arguments.
+    pub fn watch(&self, arguments: &HashMap<String, String>) -> Result<Vec<String>, Error> {
+        self.watch
+            .iter()
+            .map(|pattern| render(pattern, arguments))
+            .collect()
+    }
+
+    /// Remember the file the workflow was read from, to resolve its relative `cwd`.
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn env_policy(&self) -> WorkflowEnvPolicy {
+        self.env_policy.unwrap_or_default()
+    }
+
+    pub fn env_allow(&self) -> &[String] {
+        &self.env_allow
+    }
+
+    /// The environment variables of the workflow, their values rendered with the given arguments.
+    pub fn env(&self, arguments: &HashMap<String, String>) -> Result<Vec<(String, String)>, Error> {
+        self.env
+            .iter()
+            .map(|(key, value)| Ok((key.to_owned(), render(value, arguments)?)))
+            .collect()
+    }
+
+    /// The directory to run the workflow in, rendered with the given arguments. A leading `~`
+    /// is the home directory and a relative path is relative to the directory of the workflow file.
+    pub fn cwd(&self, arguments: &HashMap<String, String>) -> Result<Option<PathBuf>, Error> {
+        let Some(cwd) = &self.cwd else {
+            return Ok(None);
+        };
+
+        let cwd = render(cwd, arguments)?;
+        let cwd = match cwd.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                Path::new(home_dir()).join(rest.trim_start_matches(['/', '\\']))
+            }
+            _ => PathBuf::from(cwd),
+        };
+
+        Ok(Some(
+            match self.path.as_deref().and_then(|path| path.parent()) {
+                Some(directory) if cwd.is_relative() => directory.join(cwd),
+                _ => cwd,
+            },
+        ))
+    }
+
+    pub fn shell(&self) -> Option<&WorkflowShell> {
+        self.shell.as_ref()
     }
 
     pub fn arguments(&self) -> &Vec<Argument> {
@@ -288,8 +1538,8 @@ impl Workflow {
     }
 
     pub fn values(&self) -> HashMap<String, Vec<String>> {
-        self.arguments
-            .iter()
+        self.all_arguments()
+            .into_iter()
             .map(|argument| {
                 (
                     argument.name().inner().to_owned(),
@@ -303,14 +1553,20 @@ impl Workflow {
             .collect::<HashMap<_, _>>()
     }
 
-    pub fn suggestion(&self, input: &str, key: &str) -> Result<Vec<String>, CustomUserError> {
+    /// Suggest values for an argument matching the input, the given recently used values first,
+    /// followed by the values of the argument.
+    pub fn suggestion(
+        &self,
+        input: &str,
+        key: &str,
+        recent: &[String],
+    ) -> Result<Vec<String>, CustomUserError> {
         let input = input.to_lowercase();
+        let values = self.values().remove(key).unwrap_or_default();
 
-        Ok(self
-            .values()
-            .get(key)
-            .unwrap_or(&Vec::new())
+        Ok(recent
             .iter()
+            .chain(values.iter().filter(|value| !recent.contains(value)))
             .filter(|value| {
                 value.to_lowercase().contains(&input)
                     || normalized_levenshtein(&input, value) >= 0.5
@@ -323,7 +1579,10 @@ impl Workflow {
 
 #[cfg(test)]
 mod tests {
+    use chrono::TimeZone;
+
     use super::*;
+    use crate::prelude::Process;
 
     #[test]
     fn test_provides_correct_simple_suggestions() {
@@ -333,12 +1592,901 @@ mod tests {
         ];
         let workflow = Workflow::slim("test", "test", arguments);
 
-        let suggestions = workflow.suggestion("test", "test").unwrap();
+        let suggestions = workflow.suggestion("test", "test", &[]).unwrap();
 
         assert_eq!(suggestions.len(), 1);
         assert_eq!(suggestions[0], "test");
     }
 
+    #[test]
+    fn test_deserialize_workflow_shell() {
+        let workflow = r#"
+            name: test
+            command: echo ${array[@]}
+            shell: bash
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert_eq!(workflow.shell(), Some(&WorkflowShell::Bash));
+        assert!(Workflow::slim("test", "test", Vec::new()).shell().is_none());
+    }
+
+    #[test]
+    fn test_deserialize_unknown_workflow_shell() {
+        let workflow = r#"
+            name: test
+            command: test
+            shell: csh
+        "#;
+
+        assert!(serde_yaml::from_str::<Workflow>(workflow).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_workflow_confirm() {
+        let workflow = r#"
+            name: test
+            command: rm -rf build
+            confirm: dangerous
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert_eq!(workflow.confirm(), WorkflowConfirm::Dangerous);
+        assert_eq!(
+            Workflow::slim("test", "test", Vec::new()).confirm(),
+            WorkflowConfirm::Always
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_env_policy() {
+        let workflow = r#"
+            name: test
+            command: aws s3 ls
+            env_policy: allowlist
+            env_allow:
+              - AWS_*
+              - KUBECONFIG
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let policy = workflow.env_policy();
+        let allow = workflow.env_allow();
+
+        assert_eq!(policy, WorkflowEnvPolicy::Allowlist);
+        assert!(policy.allows("PATH", allow));
+        assert!(policy.allows("AWS_PROFILE", allow));
+        assert!(policy.allows("KUBECONFIG", allow));
+        assert!(!policy.allows("KUBECONFIG_BACKUP", allow));
+        assert!(!policy.allows("GITHUB_TOKEN", allow));
+        assert!(!WorkflowEnvPolicy::Clean.allows("AWS_PROFILE", allow));
+        assert!(WorkflowEnvPolicy::Inherit.allows("GITHUB_TOKEN", allow));
+        assert_eq!(
+            Workflow::slim("test", "test", Vec::new()).env_policy(),
+            WorkflowEnvPolicy::Inherit
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_vars() {
+        let workflow = r#"
+            name: test
+            command: docker push app:{{vars.sha}}-{{vars.version}}
+            vars:
+              - name: sha
+                command: git rev-parse --short HEAD
+              - name: version
+                command: cat Cargo.toml
+                regex: '(?m)^version = "(.+)"$'
+              - name: banner
+                command: cat banner.txt
+                trim: false
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let [sha, version, banner] = workflow.vars() else {
+            panic!("Expected three variables");
+        };
+
+        assert_eq!(sha.extract("  1a2b3c4\n").unwrap(), "1a2b3c4");
+        assert_eq!(
+            version.extract("[package]\nversion = \"0.1.0\"\n").unwrap(),
+            "0.1.0"
+        );
+        assert!(matches!(
+            version.extract("[package]\n"),
+            Err(Error::InvalidCommand(_))
+        ));
+        assert_eq!(banner.extract(" hello\n").unwrap(), " hello\n");
+
+        let vars = BTreeMap::from([
+            ("sha".to_string(), "1a2b3c4".to_string()),
+            ("version".to_string(), "0.1.0".to_string()),
+        ]);
+        assert_eq!(
+            workflow
+                .command()
+                .unwrap()
+                .replace(&HashMap::new(), &vars, &WorkflowShell::Sh)
+                .unwrap(),
+            "docker push app:1a2b3c4-0.1.0"
+        );
+    }
+
+    /// Values trying to break out of the word they are inserted as.
+    const ADVERSARIAL_VALUES: [&str; 19] = [
+        "it's",
+        "two words",
+        "$HOME",
+        "`id`",
+        "$(id)",
+        "; echo injected",
+        "'; echo injected; '",
+        "\"double\" quotes",
+        "back\\slash\\'",
+        "\\'; echo injected #",
+        "new\nline",
+        "*",
+        "~",
+        "{a,b}",
+        "# comment",
+        "a && b || c | d > e",
+        "-n",
+        "ünïcödé",
+        "",
+    ];
+
+    fn render_with(template: &str, value: &str, shell: WorkflowShell) -> String {
+        let arguments = HashMap::from([("value".to_string(), value.to_string())]);
+        WorkflowCommand::from(template)
+            .replace(&arguments, &BTreeMap::new(), &shell)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_replace_quotes_values() {
+        assert_eq!(
+            render_with("echo {{value}}", "1.2.3", WorkflowShell::Sh),
+            "echo 1.2.3"
+        );
+        assert_eq!(
+            render_with("echo {{value}}", "a b", WorkflowShell::Sh),
+            "echo 'a b'"
+        );
+        assert_eq!(
+            render_with("echo {{value}}", "", WorkflowShell::Sh),
+            "echo ''"
+        );
+        assert_eq!(
+            render_with("echo {{quote value}}", "1.2.3", WorkflowShell::Sh),
+            "echo '1.2.3'"
+        );
+        assert_eq!(
+            render_with("echo {{raw value}}", "$HOME; ls", WorkflowShell::Sh),
+            "echo $HOME; ls"
+        );
+        assert_eq!(
+            render_with("echo {{value}}", "it's", WorkflowShell::Bash),
+            r"echo 'it'\''s'"
+        );
+        assert_eq!(
+            render_with("echo {{value}}", r"it's a \", WorkflowShell::Fish),
+            r"echo 'it\'s a \\'"
+        );
+        assert_eq!(
+            render_with("echo {{value}}", "it's \u{2019}", WorkflowShell::Pwsh),
+            "echo 'it''s \u{2019}\u{2019}'"
+        );
+        assert_eq!(
+            shell_words::split(&render_with(
+                "echo {{value}}",
+                "a 'b' \"c\"",
+                WorkflowShell::Exec
+            ))
+            .unwrap(),
+            vec!["echo", "a 'b' \"c\""]
+        );
+    }
+
+    #[test]
+    fn test_replace_values_within_quotes() {
+        assert_eq!(
+            render_with("echo \"Hello {{value}}!\"", "Jane Doe", WorkflowShell::Sh),
+            "echo \"Hello Jane Doe!\""
+        );
+        assert_eq!(
+            render_with("echo \"{{value}}\"", "\"$HOME\" `id`", WorkflowShell::Bash),
+            r#"echo "\"\$HOME\" \`id\`""#
+        );
+        assert_eq!(
+            render_with("echo 'Hello {{value}}'", "it's", WorkflowShell::Sh),
+            r"echo 'Hello it'\''s'"
+        );
+        assert_eq!(
+            render_with(
+                "echo \"{{value}}\" '{{value}}'",
+                r#"it's "$a" \"#,
+                WorkflowShell::Fish
+            ),
+            r#"echo "it's \"\$a\" \\" 'it\'s "$a" \\'"#
+        );
+        assert_eq!(
+            render_with(
+                "echo \"{{value}}\" '{{value}}'",
+                "it's \"$a\"",
+                WorkflowShell::Pwsh
+            ),
+            "echo \"it's `\"`$a`\"\" 'it''s \"$a\"'"
+        );
+        // A quote within a comment or escaped quotes nothing
+        assert_eq!(
+            render_with("# don't\necho \\'{{value}}", "a b", WorkflowShell::Sh),
+            "# don't\necho \\''a b'"
+        );
+    }
+
+    #[test]
+    fn test_replace_values_like_before_escaping() {
+        // Commands written before values were escaped quote them already
+        let workflow =
+            serde_yaml::from_str::<Workflow>(include_str!("../../specs/echo.yml")).unwrap();
+        let arguments = HashMap::from([
+            ("sshKeyPath".to_string(), "~/.ssh/id_rsa".to_string()),
+            ("userName".to_string(), "Jane Doe".to_string()),
+            ("userEmail".to_string(), "jane@example.com".to_string()),
+        ]);
+
+        assert_eq!(
+            workflow
+                .command()
+                .unwrap()
+                .replace(&arguments, &BTreeMap::new(), &WorkflowShell::Sh)
+                .unwrap(),
+            "echo \"This is a cool echo to try out: ~/.ssh/id_rsa and User: Jane Doe <jane@example.com>\""
+        );
+    }
+
+    /// The shells among the given ones that are installed.
+    fn installed(shells: &[WorkflowShell]) -> impl Iterator<Item = WorkflowShell> + '_ {
+        shells
+            .iter()
+            .copied()
+            .filter(|shell| shell.program().and_then(Process::find).is_some())
+    }
+
+    /// The output of the command run by the shell.
+    fn run_with(command: &str, shell: WorkflowShell) -> String {
+        let output = std::process::Command::new(shell.program().unwrap())
+            .args(["-c", command])
+            .output()
+            .unwrap();
+
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    #[test]
+    fn test_replace_values_within_ansi_c_quotes() {
+        assert_eq!(
+            render_with(
+                "echo $'{{value}}'",
+                r"\'; touch pwned #",
+                WorkflowShell::Bash
+            ),
+            r"echo $'\\'\'$'; touch pwned #'"
+        );
+        // Without them, `$` is kept and the quotes are single ones
+        assert_eq!(
+            render_with("echo \"$'{{value}}'\"", "it's", WorkflowShell::Bash),
+            "echo \"$'it's'\""
+        );
+
+        for shell in installed(&[WorkflowShell::Bash, WorkflowShell::Zsh]) {
+            for value in ADVERSARIAL_VALUES {
+                let command = render_with("printf %s $'<{{value}}>'", value, shell);
+
+                assert_eq!(
+                    run_with(&command, shell),
+                    format!("<{}>", value),
+                    "{} with {}",
+                    command,
+                    shell
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_values_within_backticks() {
+        assert_eq!(
+            render_with(
+                "echo \"`echo {{value}}`\"",
+                "a; touch pwned",
+                WorkflowShell::Sh
+            ),
+            "echo \"`echo 'a; touch pwned'`\""
+        );
+        assert_eq!(
+            render_with("echo `echo {{value}}`", "a`b", WorkflowShell::Sh),
+            r"echo `echo 'a\`b'`"
+        );
+
+        for shell in installed(&[WorkflowShell::Sh, WorkflowShell::Bash, WorkflowShell::Zsh]) {
+            for value in ADVERSARIAL_VALUES {
+                for template in [
+                    "printf %s \"`printf %s {{value}}`\"",
+                    "printf %s \"`printf %s '<{{value}}>'`\"",
+                    "printf %s \"`printf %s \\\"<{{value}}>\\\"`\"",
+                ] {
+                    let command = render_with(template, value, shell);
+                    let expected = match template.contains('<') {
+                        true => format!("<{}>", value),
+                        false => value.to_string(),
+                    };
+
+                    assert_eq!(
+                        run_with(&command, shell),
+                        expected,
+                        "{} with {}",
+                        command,
+                        shell
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_values_within_heredocs() {
+        assert_eq!(
+            render_with(
+                "cat <<EOF\n{{value}}\nEOF",
+                "$(touch pwned)",
+                WorkflowShell::Sh
+            ),
+            "cat <<EOF\n\\$(touch pwned)\nEOF"
+        );
+        assert_eq!(
+            render_with(
+                "cat <<'EOF'\n{{value}}\nEOF\necho {{value}}",
+                "$a",
+                WorkflowShell::Sh
+            ),
+            "cat <<'EOF'\n$a\nEOF\necho '$a'"
+        );
+        // A value cannot end the here-document early, nor be its delimiter
+        for (template, value) in [
+            ("cat <<EOF\n{{value}}\nEOF", "EOF"),
+            ("cat <<-EOF\n{{value}}\nEOF", "a\n\tEOF\necho injected"),
+            ("cat <<{{value}}\na\nEOF", "EOF"),
+        ] {
+            let arguments = HashMap::from([("value".to_string(), value.to_string())]);
+            assert!(WorkflowCommand::from(template)
+                .replace(&arguments, &BTreeMap::new(), &WorkflowShell::Sh)
+                .is_err());
+        }
+
+        for shell in installed(&[WorkflowShell::Sh, WorkflowShell::Bash, WorkflowShell::Zsh]) {
+            for value in ADVERSARIAL_VALUES {
+                for template in [
+                    "cat <<EOF\n<{{value}}>\nEOF",
+                    "cat <<'EOF'\n<{{value}}>\nEOF",
+                    "cat <<-\"EOF\"\n\t<{{value}}>\n\tEOF",
+                    "cat <<EOF\n<$(printf %s {{value}})>\nEOF\necho",
+                    "cat <<A <<B\n<{{value}}>\nA\n<{{value}}>\nB",
+                ] {
+                    let command = render_with(template, value, shell);
+                    // With two here-documents, the last one is the input of `cat`
+                    let expected = match template.ends_with("echo") {
+                        true => format!("<{}>\n\n", value),
+                        false => format!("<{}>\n", value),
+                    };
+
+                    assert_eq!(
+                        run_with(&command, shell),
+                        expected,
+                        "{} with {}",
+                        command,
+                        shell
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_values_within_comments() {
+        assert_eq!(
+            render_with("echo hi # {{value}}", "a b", WorkflowShell::Sh),
+            "echo hi # a b"
+        );
+
+        for shell in installed(&[
+            WorkflowShell::Sh,
+            WorkflowShell::Bash,
+            WorkflowShell::Zsh,
+            WorkflowShell::Fish,
+        ]) {
+            for value in ADVERSARIAL_VALUES {
+                let command = render_with("printf %s hi # {{value}}", value, shell);
+
+                assert_eq!(
+                    run_with(&command, shell),
+                    "hi",
+                    "{} with {}",
+                    command,
+                    shell
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_adversarial_values() {
+        let shells = installed(&[
+            WorkflowShell::Sh,
+            WorkflowShell::Bash,
+            WorkflowShell::Zsh,
+            WorkflowShell::Fish,
+        ]);
+
+        for shell in shells {
+            for value in ADVERSARIAL_VALUES {
+                for template in [
+                    "printf %s {{value}}",
+                    "printf %s {{quote value}}",
+                    "# don't\nprintf %s {{value}}",
+                    // Values already within quotes, as in the commands written before values
+                    // were escaped
+                    "printf %s \"<{{value}}>\"",
+                    "printf %s '<{{value}}>'",
+                    "printf %s \"$(printf %s \"<{{value}}>\")\"",
+                ] {
+                    let command = render_with(template, value, shell);
+                    let expected = match template.contains('<') {
+                        true => format!("<{}>", value),
+                        false => value.to_string(),
+                    };
+
+                    assert_eq!(
+                        run_with(&command, shell),
+                        expected,
+                        "{} with {}",
+                        command,
+                        shell
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_workflow_invalid_regex() {
+        let workflow = r#"
+            name: test
+            command: echo {{vars.id}}
+            vars:
+              - name: id
+                command: echo 1
+                regex: "(unclosed"
+        "#;
+
+        assert!(serde_yaml::from_str::<Workflow>(workflow).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_workflow_condition() {
+        let workflow = r#"
+            name: test
+            if: env.CI
+            steps:
+              - name: migrate
+                if: args.target == 'production'
+                command: ./migrate.sh
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let arguments = HashMap::from([("target".to_string(), "staging".to_string())]);
+        let scope = Scope::new(&arguments, HashMap::new(), Path::new("."));
+
+        assert_eq!(workflow.condition().unwrap().inner(), "env.CI");
+        assert!(!workflow.condition().unwrap().evaluate(&scope).unwrap());
+        assert!(!workflow.steps()[0]
+            .condition()
+            .unwrap()
+            .evaluate(&scope)
+            .unwrap());
+        assert!(
+            serde_yaml::from_str::<Workflow>("{ name: test, command: test, if: 'args.' }").is_err()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_hooks() {
+        let workflow = r#"
+            name: test
+            command: curl localhost:{{port}}
+            arguments:
+              - name: port
+            before:
+              - kubectl port-forward svc/api {{port}}:80 > /dev/null 2>&1 &
+            after:
+              - pkill -f 'port-forward svc/api'
+            on_failure:
+              - ./notify.sh failed
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let arguments = HashMap::from([("port".to_string(), "8080".to_string())]);
+
+        assert_eq!(
+            workflow.before()[0]
+                .replace(&arguments, &BTreeMap::new(), &WorkflowShell::Sh)
+                .unwrap(),
+            "kubectl port-forward svc/api 8080:80 > /dev/null 2>&1 &"
+        );
+        assert_eq!(workflow.after().len(), 1);
+        assert_eq!(workflow.on_failure()[0].inner(), "./notify.sh failed");
+    }
+
+    #[test]
+    fn test_deserialize_workflow_matrix() {
+        let workflow = r#"
+            name: test
+            command: ./deploy.sh {{env}} {{region}}
+            arguments:
+              - name: env
+                repeat: true
+                values: [staging, production]
+            matrix:
+              region: [eu, us]
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert!(workflow.arguments()[0].repeat());
+        assert_eq!(workflow.matrix()["region"], vec!["eu", "us"]);
+        assert!(workflow.validate().is_ok());
+
+        let workflow = "{ name: test, command: test, matrix: { region: [] } }";
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        assert!(matches!(workflow.validate(), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_deserialize_workflow_watch() {
+        let workflow = r#"
+            name: test
+            command: cargo test
+            arguments:
+              - name: crate
+            watch:
+              - "{{crate}}/src/**/*.rs"
+              - Cargo.toml
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let arguments = HashMap::from([("crate".to_string(), "core".to_string())]);
+
+        assert_eq!(
+            workflow.watch(&arguments).unwrap(),
+            vec!["core/src/**/*.rs", "Cargo.toml"]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_schedule() {
+        let workflow = r#"
+            name: backup
+            command: ./backup.sh {{target}}
+            arguments:
+              - name: target
+            schedule:
+              - cron: 0 2 * * *
+                arguments:
+                  target: production
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let schedule = &workflow.schedule()[0];
+        let time = Local.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap();
+
+        assert_eq!(schedule.arguments()["target"], "production");
+        assert_eq!(
+            schedule.cron().next_after(&time),
+            Some(Local.with_ymd_and_hms(2024, 5, 2, 2, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_invalid_cron() {
+        let workflow = r#"
+            name: backup
+            command: ./backup.sh
+            schedule:
+              - cron: every night
+        "#;
+
+        assert!(serde_yaml::from_str::<Workflow>(workflow).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_workflow_steps() {
+        let workflow = r#"
+            name: release
+            arguments:
+              - name: version
+            steps:
+              - name: build
+                command: cargo build --release
+              - name: tag
+                command: git tag {{version}} -m {{message}}
+                arguments:
+                  - name: message
+                    default_value: Release
+                continue_on_error: true
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let steps = workflow.steps();
+
+        assert!(workflow.validate().is_ok());
+        assert!(workflow.is_multi_step());
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].name().inner(), "build");
+        assert!(!steps[0].continue_on_error());
+        assert!(steps[1].continue_on_error());
+
+        let names = workflow
+            .all_arguments()
+            .iter()
+            .map(|argument| argument.name().inner())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["version", "message"]);
+    }
+
+    #[test]
+    fn test_single_command_is_a_single_step() {
+        let workflow = Workflow::slim("echo", "echo hello", Vec::new());
+        let steps = workflow.steps();
+
+        assert!(!workflow.is_multi_step());
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].name().inner(), "echo");
+        assert_eq!(steps[0].command().inner(), "echo hello");
+    }
+
+    #[test]
+    fn test_validate_workflow_with_command_and_steps() {
+        let workflow = r#"
+            name: test
+            command: test
+            steps:
+              - name: test
+                command: test
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert!(matches!(workflow.validate(), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_validate_workflow_without_command() {
+        let workflow = serde_yaml::from_str::<Workflow>("name: test").unwrap();
+
+        assert!(matches!(workflow.validate(), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_validate_workflow_with_duplicated_steps() {
+        let workflow = r#"
+            name: test
+            steps:
+              - name: build
+                command: make
+              - name: build
+                command: make install
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert!(matches!(workflow.validate(), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_validate_workflow_with_step_needs() {
+        let workflow = r#"
+            name: test
+            concurrency: 2
+            steps:
+              - name: deploy
+                command: make deploy
+                needs: [build, test]
+              - name: test
+                command: make test
+                needs: [build]
+              - name: build
+                command: make
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let names = workflow
+            .steps()
+            .iter()
+            .map(|step| step.name().inner().to_owned())
+            .collect::<Vec<String>>();
+
+        assert!(workflow.validate().is_ok());
+        assert_eq!(workflow.concurrency(), NonZeroUsize::new(2));
+        assert_eq!(names, vec!["build", "test", "deploy"]);
+    }
+
+    #[test]
+    fn test_validate_workflow_with_unknown_need() {
+        let workflow = r#"
+            name: test
+            steps:
+              - name: build
+                command: make
+                needs: [configure]
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        assert!(matches!(workflow.validate(), Err(Error::InvalidCommand(_))));
+    }
+
+    #[test]
+    fn test_validate_workflow_with_cyclic_needs() {
+        let workflow = r#"
+            name: test
+            steps:
+              - name: build
+                command: make
+                needs: [test]
+              - name: test
+                command: make test
+                needs: [build]
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+
+        let Err(Error::InvalidCommand(Some(error))) = workflow.validate() else {
+            panic!("Expected an invalid command");
+        };
+        assert!(error.to_string().ends_with("build -> test -> build"));
+    }
+
+    #[test]
+    fn test_render_workflow_env_and_cwd() {
+        let workflow = r#"
+            name: test
+            command: aws s3 ls
+            env:
+              AWS_PROFILE: "{{profile}}"
+              GREETING: "it's {{profile}}"
+            cwd: "{{repo}}/infra"
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow)
+            .unwrap()
+            .with_path(Path::new("/workflows/aws.yml"));
+        let arguments = HashMap::from([
+            ("profile".to_string(), "dev".to_string()),
+            ("repo".to_string(), "projects".to_string()),
+        ]);
+
+        assert_eq!(
+            workflow.env(&arguments).unwrap(),
+            vec![
+                ("AWS_PROFILE".to_string(), "dev".to_string()),
+                ("GREETING".to_string(), "it's dev".to_string()),
+            ]
+        );
+        assert_eq!(
+            workflow.cwd(&arguments).unwrap(),
+            Some(PathBuf::from("/workflows/projects/infra"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_workflow_cwd() {
+        let workflow = |cwd: &str| {
+            serde_yaml::from_str::<Workflow>(&format!("name: test\ncommand: ls\ncwd: \"{}\"", cwd))
+                .unwrap()
+                .with_path(Path::new("/workflows/ls.yml"))
+        };
+        let arguments = HashMap::new();
+
+        assert_eq!(
+            workflow("~").cwd(&arguments).unwrap(),
+            Some(PathBuf::from(home_dir()))
+        );
+        assert_eq!(
+            workflow("~/code").cwd(&arguments).unwrap(),
+            Some(Path::new(home_dir()).join("code"))
+        );
+        assert_eq!(
+            workflow("/tmp").cwd(&arguments).unwrap(),
+            Some(PathBuf::from("/tmp"))
+        );
+        assert_eq!(
+            workflow("~other").cwd(&arguments).unwrap(),
+            Some(PathBuf::from("/workflows/~other"))
+        );
+        assert!(Workflow::slim("test", "ls", Vec::new())
+            .cwd(&arguments)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_workflow_duration() {
+        let duration = |s: &str| WorkflowDuration::from_str(s).map(|d| d.inner());
+
+        assert_eq!(duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(duration("10").unwrap(), Duration::from_secs(10));
+        assert!(duration("10 days").is_err());
+        assert!(duration("s").is_err());
+        assert_eq!(
+            WorkflowDuration::new(Duration::from_secs(90)).to_string(),
+            "90s"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_workflow_timeout_and_retry() {
+        let workflow = r#"
+            name: test
+            command: curl https://example.com
+            timeout: 30
+            retry:
+              attempts: 4
+              delay: 500ms
+              backoff: exponential
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let retry = workflow.retry().unwrap();
+
+        assert_eq!(
+            workflow.timeout().map(|timeout| timeout.inner()),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(retry.attempts().get(), 4);
+        assert_eq!(retry.delay_after(1), Duration::from_millis(500));
+        assert_eq!(retry.delay_after(2), Duration::from_secs(1));
+        assert_eq!(retry.delay_after(3), Duration::from_secs(2));
+
+        let serialized = serde_json::to_string(&workflow).unwrap();
+        let workflow = serde_json::from_str::<Workflow>(&serialized).unwrap();
+        assert_eq!(workflow.retry(), Some(retry));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = |backoff| WorkflowRetry::new(NonZeroU32::MIN, Duration::from_secs(2), backoff);
+
+        assert_eq!(
+            retry(WorkflowBackoff::Fixed).delay_after(3),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            retry(WorkflowBackoff::Linear).delay_after(3),
+            Duration::from_secs(6)
+        );
+        assert_eq!(
+            retry(WorkflowBackoff::Exponential).delay_after(3),
+            Duration::from_secs(8)
+        );
+    }
+
+    #[test]
+    fn test_provides_recent_suggestions_first() {
+        let arguments = vec![Argument::slim(
+            "branch",
+            None,
+            vec!["main", "develop", "release"],
+        )];
+        let workflow = Workflow::slim("test", "test", arguments);
+        let recent = vec!["feature/login".to_string(), "release".to_string()];
+
+        let suggestions = workflow.suggestion("", "branch", &recent).unwrap();
+
+        assert_eq!(
+            suggestions,
+            vec!["feature/login", "release", "main", "develop"]
+        );
+    }
+
     #[test]
     fn test_provides_correct_complex_suggestions() {
         let arguments = vec![Argument::slim(
@@ -352,7 +2500,7 @@ mod tests {
             ],
         )];
         let workflow = Workflow::slim("test", "test", arguments);
-        let suggestions = workflow.suggestion("erg", "test").unwrap();
+        let suggestions = workflow.suggestion("erg", "test", &[]).unwrap();
 
         assert_eq!(suggestions.len(), 2);
         assert_eq!(suggestions[0], "tergiversation");
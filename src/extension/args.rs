@@ -25,9 +25,27 @@ impl Prepare for Run {
     type Error = Error;
 
     fn prepare(&self) -> Result<Workflow, Error> {
-        let id = self.name().trim().to_lowercase().replace(['-', ' '], "_");
+        let id = WorkflowId::from(self.name());
 
-        STORE.get(&id)?.ok_or(Error::InvalidName(None))
+        STORE.get(id.inner())?.ok_or(Error::InvalidName(None))
+    }
+}
+
+impl Prepare for Pipe {
+    type Output = Vec<Workflow>;
+    type Error = Error;
+
+    fn prepare(&self) -> Result<Vec<Workflow>, Error> {
+        self.names()
+            .iter()
+            .map(|name| {
+                STORE
+                    .get(WorkflowId::from(name.as_str()).inner())?
+                    .ok_or(Error::InvalidName(Some(
+                        format!("No workflow named `{}`", name).into(),
+                    )))
+            })
+            .collect()
     }
 }
 
@@ -70,7 +88,7 @@ mod tests {
 
         let binding = result.as_ref().unwrap().clone().id();
         let id = binding.inner();
-        let command = result.as_ref().unwrap().command().inner();
+        let command = result.as_ref().unwrap().command().unwrap().inner();
 
         assert_eq!(id, "echo.yml");
         assert_eq!(command, "echo \"This is a cool echo to try out: {{sshKeyPath}} and User: {{userName}} <{{userEmail}}>\"");
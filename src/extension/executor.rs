@@ -1,22 +1,62 @@
-use std::{cmp::Ordering, collections::HashSet, fs::OpenOptions};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, Read, Write},
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        mpsc::{self, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, Local, Utc};
 use crossterm::{
     execute,
     style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
-    terminal::{self, Clear, ClearType, SetSize},
+    terminal::{Clear, ClearType},
 };
 use inquire::{required, Confirm, CustomType, Text};
+use serde_json::json;
 use strsim::normalized_levenshtein;
 
 use crate::{
     domain::{error::Error, workflow::Workflow},
     prelude::{
-        Argument, ArgumentValue, Create, List, Output, Prepare, RawVec, Reset, Run, Search, Store,
-        Unit, WorkflowDescription, WorkflowTag, STORE, WORKDIR,
+        cancel, combinations, interrupted, is_running, resolve_arguments, resolve_repeated, resume,
+        run_pipeline, terminate, wait_detached, Argument, ArgumentValue, Attempt, Crawler, Create,
+        Daemon, ExitStatus, History, HistoryEntry, HistoryStatus, HistoryStore, Init, InitShell,
+        Job, JobStatus, JobStore, Jobs, Kill, List, Logs, Output, Pipe, Prepare, Process, RawVec,
+        Rerun, Reset, Run, RunJob, ScheduleStore, ScheduledRun, Scheduler, Scope, Search, Step,
+        StepState, Store, TerminalState, Unit, Watcher, WorkStore, WorkflowCommand,
+        WorkflowCondition, WorkflowConfirm, WorkflowDescription, WorkflowDuration,
+        WorkflowEnvPolicy, WorkflowId, WorkflowSchedule, WorkflowShell, WorkflowTag, DETECTOR,
+        INDEX_DIR, JOBS_DIR, SCRIPT_HELPERS, SHELL, STORE, TEMPLATE_HELPERS, WORKDIR,
     },
 };
 
-use super::prelude::Parser;
+use super::prelude::{Parser, Precedence, RecentValueStore, PLACEHOLDER};
+
+/// The file a detached process reads its job from.
+const JOB_FILE: &str = "job.json";
+/// The file the standard output of a job is written to.
+const STDOUT_LOG: &str = "stdout.log";
+/// The file the standard error of a job is written to.
+const STDERR_LOG: &str = "stderr.log";
+/// The file the exit code of a job is written to, once it is done.
+const EXIT_CODE_FILE: &str = "exit_code";
+/// How often `workflow logs --follow` looks for more output.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+/// How long `workflow scheduler` waits at most before checking the schedules again, to pick up
+/// the workflows that changed.
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+/// How often a watched workflow checks whether `workflow` was interrupted.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
 
 pub trait Executor {
     /// The error type
@@ -42,26 +82,361 @@ impl Executor for Run {
     type Args = Workflow;
 
     fn execute(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let precedence = args.try_parse(())?;
-        let command = args.command().replace(&precedence)?;
-        let (cols, rows) = terminal::size().map_err(|e| Error::Io(Some(e.into())))?;
-        let text = format!(
-            "{}{}{}{}",
-            SetForegroundColor(Color::Green), // Set the text color to red
-            "Command to execute: ",
-            command,
-            ResetColor // Reset the text color to default
+        let is_interactive = io::stdin().is_terminal();
+        let shell = args.shell().unwrap_or(&SHELL);
+        let repeated = resolve_repeated(&args, self.arguments(), is_interactive)?;
+        let values = combinations(&repeated);
+        // A job only keeps one value per argument
+        if self.detach() && values.len() > 1 {
+            return Err(Error::InvalidArguments(Some(
+                "A run over several combinations of values cannot be detached".into(),
+            )));
+        }
+
+        // The other arguments are resolved once, the repeated ones taking the values of the
+        // first combination meanwhile
+        let mut provided = self.try_parse(())?;
+        provided.retain(|name, _| !repeated.iter().any(|(repeated, _)| repeated == name));
+        let all_arguments = args.all_arguments();
+        provided.extend(
+            values[0]
+                .iter()
+                .filter(|(name, _)| {
+                    all_arguments
+                        .iter()
+                        .any(|argument| argument.name().inner() == name)
+                })
+                .cloned(),
         );
+        let resolved = args.try_parse(provided)?;
+
+        let is_matrix = values.len() > 1;
+        let mut combinations = Vec::new();
+        for values in values {
+            let mut precedence = resolved.clone();
+            precedence.extend(values.iter().cloned());
+            let scope = scope(&args, &precedence)?;
+            if let Some(condition) = args.condition() {
+                if !condition.evaluate(&scope)? {
+                    eprintln!(
+                        "{}Skipped workflow `{}`{}, `{}` does not hold{}",
+                        SetForegroundColor(Color::Yellow),
+                        args.name().inner(),
+                        match is_matrix {
+                            true => format!(" with {}", label(&values, true)),
+                            false => String::new(),
+                        },
+                        condition,
+                        ResetColor
+                    );
+                    continue;
+                }
+            }
+
+            let (steps, skipped) = split_steps(&args, &scope)?;
+            drop(scope);
+            let env = args.env(&precedence)?;
+            let cwd = args.cwd(&precedence)?;
+
+            let mut combination = Combination {
+                values,
+                precedence,
+                vars: BTreeMap::new(),
+                env,
+                cwd,
+                steps: steps
+                    .into_iter()
+                    .map(|step| (step, String::new()))
+                    .collect(),
+                skipped,
+                before: Vec::new(),
+                after: Vec::new(),
+                on_failure: Vec::new(),
+            };
+            // Every step is rendered up front, so a broken template cannot stop a run halfway.
+            // The commands of the variables only run once the run is confirmed, but printing
+            // the command needs their value right away.
+            combination = match self.print() {
+                Some(_) => combination.with_vars(&args, shell, self.clean_env())?,
+                None => {
+                    combination.render(&args, shell, &pending_vars(&args))?;
+                    combination
+                }
+            };
+            combinations.push(match is_matrix {
+                true => combination.renamed(),
+                false => combination,
+            });
+        }
+
+        let Some(first) = combinations.first() else {
+            return Ok(Output::new("skipped", args.id().inner()).with_data(json!({
+                "workflow": args.id().inner(),
+                "arguments": resolved,
+                "if": args.condition().map(WorkflowCondition::inner),
+                "executed": false,
+            })));
+        };
+        let precedence = &first.precedence;
+        let vars = &first.vars;
+
+        let steps = combinations
+            .iter()
+            .flat_map(|combination| &combination.steps)
+            .collect::<Vec<&(Step, String)>>();
+        let skipped = combinations
+            .iter()
+            .flat_map(|combination| &combination.skipped)
+            .collect::<Vec<&Step>>();
+        let skipped_json = skipped
+            .iter()
+            .map(|step| {
+                json!({
+                    "step": step.name().inner(),
+                    "if": step.condition().map(WorkflowCondition::inner),
+                })
+            })
+            .collect::<Vec<_>>();
+        let command = steps
+            .iter()
+            .map(|(_, command)| command.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let with_combinations = |mut data: serde_json::Value| {
+            if is_matrix {
+                data["combinations"] = json!(combinations
+                    .iter()
+                    .map(Combination::to_json)
+                    .collect::<Vec<_>>());
+            }
+            data
+        };
+
+        if let Some(fd) = self.print() {
+            let mut printer = printer(fd)?;
+            write!(printer, "{}", command)
+                .and_then(|_| printer.flush())
+                .map_err(|e| Error::Io(Some(e.into())))?;
+
+            remember_values(&args, precedence);
+            return Ok(
+                Output::new("print", &command).with_data(with_combinations(json!({
+                    "workflow": args.id().inner(),
+                    "arguments": precedence,
+                    "vars": vars,
+                    "skipped": skipped_json,
+                    "command": command,
+                }))),
+            );
+        }
+
+        for combination in &combinations {
+            check_cwd(&args, combination.cwd.as_deref())?;
+        }
+
+        let _terminal = TerminalState::save();
+        let dangers = steps
+            .iter()
+            .map(|(_, command)| DETECTOR.find(command))
+            .collect::<Vec<Vec<Range<usize>>>>();
+        let hooks = [Hook::Before, Hook::OnFailure, Hook::After]
+            .into_iter()
+            .map(|hook| {
+                let commands = hook_commands(&combinations, hook)
+                    .into_iter()
+                    .map(|(_, command)| (command, DETECTOR.find(command)))
+                    .collect::<Vec<(&str, Vec<Range<usize>>)>>();
+                (hook, commands)
+            })
+            .collect::<Vec<_>>();
+        let var_commands = combinations
+            .iter()
+            .map(|combination| {
+                let commands = var_commands(&args, &combination.precedence, shell)?;
+                Ok(commands.into_iter().map(move |(name, command)| {
+                    let parts = DETECTOR.find(&command);
+                    (combination, name, command, parts)
+                }))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<(&Combination, String, String, Vec<Range<usize>>)>>();
+        let is_dangerous = dangers.iter().any(|parts| !parts.is_empty())
+            || hooks
+                .iter()
+                .flat_map(|(_, commands)| commands)
+                .any(|(_, parts)| !parts.is_empty())
+            || var_commands
+                .iter()
+                .any(|(_, _, _, parts)| !parts.is_empty());
+        let text = if args.is_multi_step() || is_matrix {
+            let steps = steps
+                .iter()
+                .zip(&dangers)
+                .enumerate()
+                .map(|(index, ((step, command), parts))| {
+                    let command = highlight(command, parts);
+                    match step.needs().is_empty() {
+                        true => format!("  {}. {}: {}", index + 1, step.name().inner(), command),
+                        false => format!(
+                            "  {}. {} (needs {}): {}",
+                            index + 1,
+                            step.name().inner(),
+                            step.needs()
+                                .iter()
+                                .map(|need| need.inner())
+                                .collect::<Vec<&str>>()
+                                .join(", "),
+                            command
+                        ),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            format!(
+                "{}Steps to execute:\n{}{}",
+                SetForegroundColor(Color::Green),
+                steps,
+                ResetColor
+            )
+        } else {
+            format!(
+                "{}{}{}{}",
+                SetForegroundColor(Color::Green), // Set the text color to red
+                "Command to execute: ",
+                highlight(
+                    &command,
+                    dangers.first().map(Vec::as_slice).unwrap_or_default()
+                ),
+                ResetColor // Reset the text color to default
+            )
+        };
+
+        let text = hooks
+            .iter()
+            .filter(|(_, commands)| !commands.is_empty())
+            .fold(text, |text, (hook, commands)| {
+                format!(
+                    "{}\n{}{}:\n{}{}",
+                    text,
+                    SetForegroundColor(Color::Green),
+                    hook.title(),
+                    commands
+                        .iter()
+                        .map(|(command, parts)| format!("  {}", highlight(command, parts)))
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                    ResetColor
+                )
+            });
+        let text = match skipped.is_empty() {
+            true => text,
+            false => format!(
+                "{}\n{}Skipped:\n{}{}",
+                text,
+                SetForegroundColor(Color::Yellow),
+                skipped
+                    .iter()
+                    .map(|step| format!(
+                        "  {}, `{}` does not hold",
+                        step.name().inner(),
+                        step.condition().map_or("", WorkflowCondition::inner)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                ResetColor
+            ),
+        };
+        // The variables are not prompted for, but whoever confirms should know how they are
+        // computed
+        let variables = var_commands
+            .iter()
+            .map(|(combination, name, command, parts)| match is_matrix {
+                true => format!(
+                    "  {}[{}]: {}",
+                    name,
+                    label(&combination.values, false),
+                    highlight(command, parts)
+                ),
+                false => format!("  {}: {}", name, highlight(command, parts)),
+            })
+            .collect::<Vec<String>>();
+        let text = match variables.is_empty() {
+            true => text,
+            false => format!(
+                "{}\nVariables, computed once confirmed:\n{}",
+                text,
+                variables.join("\n")
+            ),
+        };
+        let directories = combinations
+            .iter()
+            .filter_map(|combination| combination.cwd.as_deref())
+            .fold(Vec::new(), |mut directories, cwd| {
+                if !directories.contains(&cwd) {
+                    directories.push(cwd);
+                }
+                directories
+            });
+        let text = match directories.is_empty() {
+            true => text,
+            false => format!(
+                "{}\nIn directory: {}",
+                text,
+                directories
+                    .iter()
+                    .map(|cwd| cwd.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
 
         println!("\n");
         println!("{}", text);
         println!("\n");
 
-        let is_execute = Confirm::new("Do you want to execute the command?")
-            .prompt()
-            .map_err(|e| Error::ReadError(Some(e.into())))?;
+        let is_confirmed = confirm(
+            args.confirm(),
+            args.name().inner(),
+            is_dangerous,
+            self.yes(),
+            is_interactive,
+        )?;
 
-        if is_execute {
+        if !is_confirmed {
+            return Ok(
+                Output::new("command", &command).with_data(with_combinations(json!({
+                    "workflow": args.id().inner(),
+                    "arguments": precedence,
+                    "vars": vars,
+                    "skipped": skipped_json,
+                    "command": command,
+                    "executed": false,
+                }))),
+            );
+        }
+
+        // The commands of the variables run now that the run is confirmed
+        let combinations = combinations
+            .into_iter()
+            .map(|combination| combination.with_vars(&args, shell, self.clean_env()))
+            .collect::<Result<Vec<Combination>, Error>>()?;
+        let first = &combinations[0];
+        let precedence = &first.precedence;
+        let command = combinations
+            .iter()
+            .flat_map(|combination| &combination.steps)
+            .map(|(_, command)| command.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        if self.detach() {
+            return detach(self, &args, precedence, &command);
+        }
+
+        if is_interactive {
             execute!(
                 std::io::stdout(),
                 Clear(ClearType::All),
@@ -70,48 +445,594 @@ impl Executor for Run {
                 Print("\n"),
             )
             .map_err(|e| Error::Io(Some(e.into())))?;
+        }
 
-            // What if the command needs a user input?
-            let output: std::process::Output = std::process::Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .output()
-                .map_err(|e| Error::Io(Some(e.into())))?;
+        let patterns = watch_patterns(self, &args, precedence, is_interactive)?;
+        let run = || {
+            execute_steps(self, &args, &combinations, &command).map(|output| {
+                let mut data = output.data().cloned().unwrap_or(json!({}));
+                data["skipped"] = json!(skipped_json);
+                output.with_data(data)
+            })
+        };
 
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let is_success = output.status.success();
+        match patterns.is_empty() {
+            true => run(),
+            false => watch(&patterns, first.cwd.as_deref(), run),
+        }
+    }
+}
+
+impl Executor for History {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let workflow = self.name().map(WorkflowId::from);
+        let entries = STORE
+            .history()?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, entry)| workflow.iter().all(|workflow| entry.workflow() == workflow))
+            .filter(|(_, entry)| match self.status() {
+                Some(HistoryStatus::Success) => entry.success(),
+                Some(HistoryStatus::Failure) => !entry.success(),
+                None => true,
+            })
+            .take(self.limit())
+            .collect::<Vec<(usize, HistoryEntry)>>();
+        let lines = entries
+            .iter()
+            .map(|(index, entry)| {
+                let color = match entry.success() {
+                    true => Color::Green,
+                    false => Color::Red,
+                };
 
-            let message = if is_success {
                 format!(
-                    "{}{}{}",
-                    SetForegroundColor(Color::Green),
-                    stdout,
-                    ResetColor
+                    "{}{:>4}{}  {}  {}{}{}  exit {}  {:.1}s\n      {}",
+                    SetForegroundColor(Color::White),
+                    index + 1,
+                    ResetColor,
+                    entry
+                        .started_at()
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    SetForegroundColor(color),
+                    entry.workflow().inner(),
+                    ResetColor,
+                    entry.exit_code(),
+                    entry.duration().as_secs_f64(),
+                    entry.command().replace('\n', "\n      "),
                 )
-            } else {
-                format!("{}{}{}", SetForegroundColor(Color::Red), stderr, ResetColor)
-            };
+            })
+            .collect::<Vec<String>>();
 
-            execute!(std::io::stdout(), Print(message)).map_err(|e| Error::Io(Some(e.into())))?;
+        if lines.is_empty() {
+            println!("No runs found");
+        } else {
+            println!("{}", lines.join("\n"));
+        }
 
-            execute!(std::io::stdout(), SetSize(cols, rows),)
-                .map_err(|e| Error::Io(Some(e.into())))?;
+        let runs = entries
+            .iter()
+            .map(|(index, entry)| {
+                let mut run = entry.to_json();
+                run["number"] = json!(index + 1);
+                run
+            })
+            .collect::<Vec<_>>();
+        Ok(Output::new("history", "success").with_data(json!({ "runs": runs })))
+    }
+}
+
+impl Executor for Rerun {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let number = self.number();
+        let entry =
+            STORE
+                .history()?
+                .into_iter()
+                .nth(number.get() - 1)
+                .ok_or(Error::InvalidArguments(Some(
+                    format!("There is no run number {} in the history", number).into(),
+                )))?;
+
+        // Workflows without `cwd` run in the directory `workflow` is called from
+        if let Some(cwd) = entry.cwd() {
+            std::env::set_current_dir(cwd).map_err(|e| Error::ReadError(Some(e.into())))?;
         }
-        Ok(Output::new("command", &command))
+
+        let command = Run::new(entry.workflow().inner())
+            .with_arguments(entry.arguments().clone().into_iter().collect())
+            .with_watch_key(false)
+            .with_yes(self.yes());
+        let workflow = command.prepare()?;
+        command.execute(workflow)
     }
 }
 
-impl Executor for List {
+impl Executor for Jobs {
     type Error = Error;
     type Output = Output;
     type Args = Unit;
 
     fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
-        let workflows = STORE.get_all();
+        let jobs = STORE
+            .jobs()?
+            .into_iter()
+            .rev()
+            .map(|job| {
+                let status = job_status(&job);
+                (job, status)
+            })
+            .collect::<Vec<(Job, JobStatus)>>();
+        let lines = jobs
+            .iter()
+            .map(|(job, status)| {
+                let color = match status {
+                    JobStatus::Running => Color::Cyan,
+                    JobStatus::Exited(0) => Color::Green,
+                    JobStatus::Exited(_) | JobStatus::Lost => Color::Red,
+                };
 
-        let workflows: Vec<String> = workflows?
+                format!(
+                    "{}{:>4}{}  {}  {}{}{}  {}\n      {}",
+                    SetForegroundColor(Color::White),
+                    job.id(),
+                    ResetColor,
+                    job.started_at()
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    SetForegroundColor(color),
+                    job.workflow().id().inner(),
+                    ResetColor,
+                    status,
+                    job.command().replace('\n', "\n      "),
+                )
+            })
+            .collect::<Vec<String>>();
+
+        if lines.is_empty() {
+            println!("No jobs found");
+        } else {
+            println!("{}", lines.join("\n"));
+        }
+
+        let jobs = jobs
+            .iter()
+            .map(|(job, status)| job.to_json(*status))
+            .collect::<Vec<_>>();
+        Ok(Output::new("jobs", "success").with_data(json!({ "jobs": jobs })))
+    }
+}
+
+impl Executor for Logs {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let job = find_job(self.job())?;
+        let directory = job_dir(job.id());
+        let open = |name: &str| {
+            File::open(directory.join(name)).map_err(|e| Error::ReadError(Some(e.into())))
+        };
+        let mut files = [open(STDOUT_LOG)?, open(STDERR_LOG)?];
+        let mut logs = [Vec::new(), Vec::new()];
+
+        loop {
+            // Checked before reading, so the output written right before the job is done is not missed
+            let is_running = self.follow() && job_status(&job) == JobStatus::Running;
+            for (index, file) in files.iter_mut().enumerate() {
+                let start = logs[index].len();
+                file.read_to_end(&mut logs[index])
+                    .and_then(|_| match index {
+                        0 => io::stdout().write_all(&logs[index][start..]),
+                        _ => io::stderr().write_all(&logs[index][start..]),
+                    })
+                    .map_err(|e| Error::Io(Some(e.into())))?;
+            }
+
+            if !is_running {
+                break;
+            }
+            thread::sleep(FOLLOW_INTERVAL);
+        }
+
+        let [stdout, stderr] = logs.map(|log| String::from_utf8_lossy(&log).into_owned());
+        Ok(Output::new("logs", "success").with_data(json!({
+            "job": job.id(),
+            "stdout": stdout,
+            "stderr": stderr,
+        })))
+    }
+}
+
+impl Executor for Kill {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let job = find_job(self.job())?;
+        let (JobStatus::Running, Some(pid)) = (job_status(&job), job.pid()) else {
+            return Err(Error::InvalidArguments(Some(
+                format!("Job {} is not running", job.id()).into(),
+            )));
+        };
+
+        let signal = terminate(pid)?;
+        // The detached process had no chance to write its exit code, so it is written for it
+        if exit_code(&job).is_none() {
+            fs::write(
+                job_dir(job.id()).join(EXIT_CODE_FILE),
+                (128 + signal).to_string(),
+            )
+            .map_err(|e| Error::WriteError(Some(e.into())))?;
+        }
+
+        println!("Killed job {}", job.id());
+        Ok(Output::new("kill", &job.id().to_string()).with_data(json!({
+            "job": job.id(),
+            "signal": signal,
+        })))
+    }
+}
+
+impl Executor for Pipe {
+    type Error = Error;
+    type Output = Output;
+    type Args = Vec<Workflow>;
+
+    fn execute(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let is_interactive = io::stdin().is_terminal();
+        // A value is given to every workflow having the argument
+        let provided = self.try_parse(())?;
+        let unknown = provided
+            .keys()
+            .filter(|key| {
+                !args.iter().any(|workflow| {
+                    workflow
+                        .all_arguments()
+                        .iter()
+                        .any(|argument| argument.name().inner() == key.as_str())
+                })
+            })
+            .map(|key| format!("`{}`", key))
+            .collect::<Vec<String>>();
+        if !unknown.is_empty() {
+            return Err(Error::InvalidArguments(Some(
+                format!("Unknown arguments: {}", unknown.join(", ")).into(),
+            )));
+        }
+
+        // The stages all start at once, so every argument is resolved before any of them runs
+        let (mut stages, steps) = args
             .into_iter()
+            .map(|workflow| {
+                let shell = workflow.shell().unwrap_or(&SHELL);
+                let provided = provided
+                    .iter()
+                    .filter(|(key, _)| {
+                        workflow
+                            .all_arguments()
+                            .iter()
+                            .any(|argument| argument.name().inner() == key.as_str())
+                    })
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                let precedence = workflow.try_parse(provided)?;
+                let scope = scope(&workflow, &precedence)?;
+                // Leaving a workflow out would feed the next one with something else
+                if let Some(condition) = workflow.condition() {
+                    if !condition.evaluate(&scope)? {
+                        return Err(Error::InvalidArguments(Some(
+                            format!(
+                                "The workflow `{}` cannot be piped, `{}` does not hold",
+                                workflow.name().inner(),
+                                condition
+                            )
+                            .into(),
+                        )));
+                    }
+                }
+                let (steps, _) = split_steps(&workflow, &scope)?;
+                let cwd = workflow.cwd(&precedence)?;
+                check_cwd(&workflow, cwd.as_deref())?;
+                // The commands of the variables only run once the pipeline is confirmed
+                let command = stage_command(&steps, &precedence, &pending_vars(&workflow), shell)?;
+
+                Ok(((workflow, precedence, command, cwd), steps))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .unzip::<_, _, Vec<_>, Vec<Vec<Step>>>();
+
+        let _terminal = TerminalState::save();
+        let dangers = stages
+            .iter()
+            .map(|(_, _, command, _)| DETECTOR.find(command))
+            .collect::<Vec<Vec<Range<usize>>>>();
+        let var_commands = stages
+            .iter()
+            .map(|(workflow, precedence, _, _)| {
+                let shell = workflow.shell().unwrap_or(&SHELL);
+                var_commands(workflow, precedence, shell)
+            })
+            .collect::<Result<Vec<Vec<(String, String)>>, Error>>()?;
+        let is_dangerous = dangers.iter().any(|parts| !parts.is_empty())
+            || var_commands
+                .iter()
+                .flatten()
+                .any(|(_, command)| !DETECTOR.find(command).is_empty());
+        // The output of the last stage is the one of `workflow`, so nothing else is printed to it
+        let lines = stages
+            .iter()
+            .zip(&dangers)
+            .zip(&var_commands)
+            .enumerate()
+            .map(|(index, (((workflow, _, command, cwd), parts), vars))| {
+                let line = format!(
+                    "  {}. {}: {}",
+                    index + 1,
+                    workflow.id().inner(),
+                    highlight(command, parts)
+                );
+                let line = match cwd {
+                    Some(cwd) => format!("{} (in {})", line, cwd.display()),
+                    None => line,
+                };
+                vars.iter().fold(line, |line, (name, command)| {
+                    let parts = DETECTOR.find(command);
+                    format!("{}\n     {}: {}", line, name, highlight(command, &parts))
+                })
+            })
+            .collect::<Vec<String>>();
+        eprintln!(
+            "\n{}Pipeline to execute:\n{}{}\n",
+            SetForegroundColor(Color::Green),
+            lines.join("\n"),
+            ResetColor
+        );
+
+        // The strictest policy of the stages applies to the whole pipeline
+        let policy = stages
+            .iter()
+            .map(|(workflow, _, _, _)| workflow.confirm())
+            .fold(WorkflowConfirm::Never, |policy, confirm| {
+                match (policy, confirm) {
+                    (WorkflowConfirm::Always, _) | (_, WorkflowConfirm::Always) => {
+                        WorkflowConfirm::Always
+                    }
+                    (WorkflowConfirm::Dangerous, _) | (_, WorkflowConfirm::Dangerous) => {
+                        WorkflowConfirm::Dangerous
+                    }
+                    (WorkflowConfirm::Never, WorkflowConfirm::Never) => WorkflowConfirm::Never,
+                }
+            });
+        let name = stages
+            .iter()
+            .map(|(workflow, _, _, _)| workflow.id().inner().to_string())
+            .collect::<Vec<String>>()
+            .join(" | ");
+        let is_confirmed = confirm(policy, &name, is_dangerous, self.yes(), is_interactive)?;
+        let command = stages
+            .iter()
+            .map(|(_, _, command, _)| command.as_str())
+            .collect::<Vec<&str>>()
+            .join(" | ");
+
+        let to_json = |stages: &[(Workflow, Precedence, String, Option<PathBuf>)],
+                       statuses: Option<&[ExitStatus]>| {
+            stages
+                .iter()
+                .enumerate()
+                .map(|(index, (workflow, precedence, command, cwd))| {
+                    let status = statuses.and_then(|statuses| statuses.get(index));
+                    json!({
+                        "workflow": workflow.id().inner(),
+                        "arguments": precedence,
+                        "command": command,
+                        "cwd": cwd,
+                        "exit_code": status.map(ExitStatus::exit_code),
+                        "status": status.map(ExitStatus::to_string),
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if !is_confirmed {
+            return Ok(Output::new("pipe", &command).with_data(json!({
+                "stages": to_json(&stages, None),
+                "executed": false,
+            })));
+        }
+
+        // The commands of the variables run now that the pipeline is confirmed
+        let processes = stages
+            .iter_mut()
+            .zip(&steps)
+            .map(|((workflow, precedence, command, cwd), steps)| {
+                let shell = workflow.shell().unwrap_or(&SHELL);
+                let vars = compute_vars(workflow, precedence, self.clean_env())?;
+                *command = stage_command(steps, precedence, &vars, shell)?;
+                let env = workflow.env(precedence)?;
+                let (env, is_clean) = environment(workflow, &env, self.clean_env());
+
+                Ok(Process::shell(shell, command)?
+                    .with_env(env)
+                    .with_clean_env(is_clean)
+                    .with_cwd(cwd.as_deref()))
+            })
+            .collect::<Result<Vec<Process>, Error>>()?;
+        let command = stages
+            .iter()
+            .map(|(_, _, command, _)| command.as_str())
+            .collect::<Vec<&str>>()
+            .join(" | ");
+
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let statuses = run_pipeline(&processes)?;
+        let duration = started.elapsed();
+
+        eprintln!();
+        for (index, ((workflow, precedence, command, cwd), status)) in
+            stages.iter().zip(&statuses).enumerate()
+        {
+            eprintln!(
+                "{}  {}. {} {}{}",
+                SetForegroundColor(match status.success() {
+                    true => Color::Green,
+                    false => Color::Red,
+                }),
+                index + 1,
+                workflow.id().inner(),
+                status,
+                ResetColor
+            );
+
+            // Every stage is recorded on its own, so it can be run again with `workflow rerun`
+            let cwd = cwd.clone().or(std::env::current_dir().ok());
+            let entry = HistoryEntry::new(
+                workflow.id(),
+                precedence,
+                command,
+                cwd.as_deref(),
+                started_at,
+                duration,
+                status.exit_code(),
+            );
+            remember_values(workflow, precedence);
+            if let Err(error) = STORE.record(&entry) {
+                eprintln!(
+                    "{}Warning: the run could not be recorded in the history: {:?}{}",
+                    SetForegroundColor(Color::Yellow),
+                    error,
+                    ResetColor
+                );
+            }
+        }
+
+        // As with `set -o pipefail`, the pipeline fails with the last stage that failed
+        let status = statuses
+            .iter()
+            .rev()
+            .find(|status| !status.success())
+            .cloned()
+            .unwrap_or(ExitStatus::new(Some(0), None));
+        Ok(Output::new("pipe", &command)
+            .with_status(status)
+            .with_data(json!({
+                "stages": to_json(&stages, Some(&statuses)),
+                "executed": true,
+                "started_at": started_at,
+                "duration": duration.as_secs_f64(),
+            })))
+    }
+}
+
+impl Executor for Daemon {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        if self.list() {
+            return list_schedules(&STORE);
+        }
+
+        let (sender, finished) = mpsc::channel::<ScheduledRun>();
+        let mut done = Vec::new();
+        let mut checked_at = Local::now();
+        println!(
+            "{}Running the workflows with a schedule, press Ctrl-C to stop{}",
+            SetForegroundColor(Color::Cyan),
+            ResetColor
+        );
+
+        loop {
+            done.extend(finished.try_iter());
+            let now = Local::now();
+            // Only opened for a moment at a time, so that `workflow` can still be used meanwhile
+            let next = match WorkStore::init(&WORKDIR.join(INDEX_DIR)).and_then(|store| {
+                record_scheduled_runs(&store, &mut done);
+                let next = run_due_schedules(&store, &checked_at, &now, &sender)?;
+                checked_at = now;
+                Ok(next)
+            }) {
+                Ok(next) => next,
+                Err(error) => {
+                    print_warning(&format!(
+                        "The schedules could not be checked, trying again in a moment: {:?}",
+                        error
+                    ));
+                    None
+                }
+            };
+
+            let timeout = next
+                .and_then(|next| (next - Local::now()).to_std().ok())
+                .unwrap_or(SCHEDULER_INTERVAL)
+                .min(SCHEDULER_INTERVAL);
+            // A run that is done wakes the scheduler up, to record it right away
+            if let Ok(run) = finished.recv_timeout(timeout) {
+                done.push(run);
+            }
+        }
+    }
+}
+
+impl Executor for RunJob {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let directory = job_dir(self.job());
+        let job = fs::read_to_string(directory.join(JOB_FILE))
+            .map_err(|e| Error::ReadError(Some(e.into())))
+            .and_then(|job| {
+                serde_json::from_str::<Job>(&job).map_err(|e| Error::ParseError(Some(e.into())))
+            })?;
+
+        // Every argument was resolved before detaching, so nothing is prompted for
+        let command = Run::new(job.workflow().name().inner())
+            .with_arguments(job.arguments().clone().into_iter().collect())
+            .with_mode(self.mode())
+            .with_jobs(self.jobs())
+            .with_clean_env(self.clean_env())
+            .with_watch_key(false)
+            .with_yes(true);
+        let output = command.execute(job.workflow().clone());
+
+        let code = match &output {
+            Ok(output) => output.exit_code(),
+            Err(error) => error.exit_code(),
+        };
+        fs::write(directory.join(EXIT_CODE_FILE), code.to_string())
+            .map_err(|e| Error::WriteError(Some(e.into())))?;
+
+        output
+    }
+}
+
+impl Executor for List {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let workflows = STORE.get_all()?;
+
+        let lines: Vec<String> = workflows
+            .iter()
             .map(|workflow| {
                 let description = workflow
                     .description()
@@ -127,7 +1048,18 @@ impl Executor for List {
                     name.inner(),
                     ResetColor
                 );
-                let command = format!("Command: {}", command.inner());
+                let command = match command {
+                    Some(command) => format!("Command: {}", command.inner()),
+                    None => format!(
+                        "Steps: {}",
+                        workflow
+                            .steps()
+                            .iter()
+                            .map(|step| step.name().inner())
+                            .collect::<Vec<&str>>()
+                            .join(", ")
+                    ),
+                };
 
                 format!(
                     "* {}{}\n{}\n{}{}{}",
@@ -141,8 +1073,8 @@ impl Executor for List {
             })
             .collect();
 
-        println!("{}", workflows.join("\n"));
-        Ok(Output::new("list", "success"))
+        println!("{}", lines.join("\n"));
+        Ok(Output::new("list", "success").with_data(json!({ "workflows": workflows })))
     }
 }
 
@@ -188,7 +1120,9 @@ impl Executor for Search {
             .prompt()
             .map_err(|e| Error::ReadError(Some(e.into())))?;
 
-        let command = Run::new(&workflow);
+        let command = Run::new(&workflow)
+            .with_print(self.print())
+            .with_yes(self.yes());
         let args = command.prepare()?;
         command.execute(args)
     }
@@ -215,15 +1149,19 @@ impl Executor for Reset {
                         SetForegroundColor(Color::Reset),
                     )
                     .map_err(|e| Error::Io(Some(e.into())))?;
-        let is_reset = Confirm::new("Do you want to reset the workflows?")
-            .prompt()
-            .map_err(|e| Error::ReadError(Some(e.into())))?;
+        let is_reset = self.yes()
+            || Confirm::new("Do you want to reset the workflows?")
+                .prompt()
+                .map_err(|e| Error::ReadError(Some(e.into())))?;
 
         if is_reset {
             STORE.clone().delete_all()?;
-            Ok(Output::new("reset", ""))
+            Ok(Output::new("reset", "").with_data(json!({ "reset": true })))
         } else {
-            Ok(Output::new("reset", "No workflows were reset"))
+            Ok(
+                Output::new("reset", "No workflows were reset")
+                    .with_data(json!({ "reset": false })),
+            )
         }
     }
 }
@@ -314,20 +1252,1435 @@ impl Executor for Create {
         )
         .map_err(|e| Error::Io(Some(e.into())))?;
 
-        Ok(Output::new("create", &format!("Workflow {} created", name)))
+        Ok(Output::new("create", &format!("Workflow {} created", name))
+            .with_data(json!({ "workflow": workflow.id().inner(), "path": path })))
+    }
+}
+
+impl Executor for Init {
+    type Error = Error;
+    type Output = Output;
+    type Args = Unit;
+
+    fn execute(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (shell, script) = match self.shell() {
+            InitShell::Bash => ("bash", include_str!("shell/workflow.bash")),
+            InitShell::Zsh => ("zsh", include_str!("shell/workflow.zsh")),
+            InitShell::Fish => ("fish", include_str!("shell/workflow.fish")),
+        };
+
+        print!("{}", script);
+        Ok(Output::new("init", shell).with_data(json!({ "script": script })))
+    }
+}
+
+/// A run of the workflow with one combination of the values of its repeated arguments and of
+/// its matrix.
+struct Combination {
+    /// The values of the repeated arguments and of the matrix, empty if there are none
+    values: Vec<(String, String)>,
+    /// Every argument, with the values of the combination
+    precedence: Precedence,
+    vars: BTreeMap<String, String>,
+    env: Vec<(String, String)>,
+    cwd: Option<PathBuf>,
+    /// The steps to run, with their rendered command
+    steps: Vec<(Step, String)>,
+    /// The steps whose `if` does not hold
+    skipped: Vec<Step>,
+    /// The rendered commands of the hooks
+    before: Vec<String>,
+    after: Vec<String>,
+    on_failure: Vec<String>,
+}
+
+impl Combination {
+    /// Name the steps after the values of the combination, e.g. `build[staging]`, so the steps
+    /// of every combination can run in the same schedule.
+    fn renamed(mut self) -> Self {
+        let suffix = label(&self.values, false);
+        let rename = |step: &Step| {
+            let name = format!("{}[{}]", step.name().inner(), suffix);
+            let needs = step
+                .needs()
+                .iter()
+                .map(|need| format!("{}[{}]", need.inner(), suffix))
+                .collect::<Vec<String>>();
+            step.clone()
+                .with_name(&name)
+                .with_needs(needs.iter().map(String::as_str).collect())
+        };
+
+        self.steps = self
+            .steps
+            .iter()
+            .map(|(step, command)| (rename(step), command.clone()))
+            .collect();
+        self.skipped = self.skipped.iter().map(rename).collect();
+        self
+    }
+
+    /// Render the commands of the steps and of the hooks with the given variables.
+    fn render(
+        &mut self,
+        workflow: &Workflow,
+        shell: &WorkflowShell,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<Unit, Error> {
+        for (step, command) in &mut self.steps {
+            *command = step.command().replace(&self.precedence, vars, shell)?;
+        }
+        let render = |hook: Hook| {
+            hook.commands(workflow)
+                .iter()
+                .map(|command| command.replace(&self.precedence, vars, shell))
+                .collect::<Result<Vec<String>, Error>>()
+        };
+        self.before = render(Hook::Before)?;
+        self.after = render(Hook::After)?;
+        self.on_failure = render(Hook::OnFailure)?;
+
+        Ok(())
+    }
+
+    /// Compute the variables of the combination, running their commands, and render its
+    /// commands with them.
+    fn with_vars(
+        mut self,
+        workflow: &Workflow,
+        shell: &WorkflowShell,
+        clean_env: bool,
+    ) -> Result<Self, Error> {
+        let vars = compute_vars(workflow, &self.precedence, clean_env)?;
+        self.render(workflow, shell, &vars)?;
+        self.vars = vars;
+
+        Ok(self)
+    }
+
+    fn hook(&self, hook: Hook) -> &[String] {
+        match hook {
+            Hook::Before => &self.before,
+            Hook::OnFailure => &self.on_failure,
+            Hook::After => &self.after,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "arguments": self.values.iter().cloned().collect::<BTreeMap<String, String>>(),
+            "vars": self.vars,
+        })
+    }
+}
+
+/// The values of a combination, e.g. `env=staging, region=eu`, or `staging, eu` without names.
+fn label(values: &[(String, String)], with_names: bool) -> String {
+    values
+        .iter()
+        .map(|(name, value)| match with_names {
+            true => format!("{}={}", name, value),
+            false => value.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// The commands of a workflow running around its steps.
+#[derive(Debug, Clone, Copy)]
+enum Hook {
+    Before,
+    OnFailure,
+    After,
+}
+
+impl Hook {
+    fn commands(self, workflow: &Workflow) -> &[WorkflowCommand] {
+        match self {
+            Hook::Before => workflow.before(),
+            Hook::OnFailure => workflow.on_failure(),
+            Hook::After => workflow.after(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Before => "before",
+            Hook::OnFailure => "on_failure",
+            Hook::After => "after",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Hook::Before => "Before",
+            Hook::OnFailure => "On failure",
+            Hook::After => "After",
+        }
+    }
+}
+
+/// What the commands of a hook did.
+#[derive(Default)]
+struct HookOutcome {
+    /// The status of the first command that failed
+    failure: Option<ExitStatus>,
+    stdout: String,
+    stderr: String,
+    commands: Vec<serde_json::Value>,
+}
+
+/// The rendered commands of the hook, in order, each of them once along with the first
+/// combination rendering it.
+fn hook_commands(combinations: &[Combination], hook: Hook) -> Vec<(&Combination, &str)> {
+    combinations
+        .iter()
+        .flat_map(|combination| {
+            combination
+                .hook(hook)
+                .iter()
+                .map(move |command| (combination, command.as_str()))
+        })
+        .fold(Vec::new(), |mut commands, (combination, command)| {
+            if !commands.iter().any(|(_, known)| *known == command) {
+                commands.push((combination, command));
+            }
+            commands
+        })
+}
+
+/// Run the commands of the hook one after the other, with the environment and in the directory
+/// of their combination. The `before` commands stop at the first failing one, while the other
+/// hooks run every command, even once `workflow` was interrupted or the run was cancelled.
+fn run_hooks(run: &Run, args: &Workflow, combinations: &[Combination], hook: Hook) -> HookOutcome {
+    let shell = args.shell().unwrap_or(&SHELL);
+    let is_cleanup = !matches!(hook, Hook::Before);
+    let mut outcome = HookOutcome::default();
+
+    for (combination, command) in hook_commands(combinations, hook) {
+        let (env, is_clean) = environment(args, &combination.env, run.clean_env());
+        // Printing must not keep the cleanup from running, so its errors are ignored
+        let _ = print_hook(Color::Cyan, hook, command, "running");
+        let status = match Process::shell(shell, command).and_then(|process| {
+            process
+                .with_env(env)
+                .with_clean_env(is_clean)
+                .with_cwd(combination.cwd.as_deref())
+                .with_cleanup(is_cleanup)
+                .run(run.mode())
+        }) {
+            Ok(captured) => {
+                outcome.stdout.push_str(captured.stdout());
+                outcome.stderr.push_str(captured.stderr());
+                captured.status().clone()
+            }
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ExitStatus::new(None, None)
+            }
+        };
+
+        let _ = match status.success() {
+            true => print_hook(Color::Green, hook, command, "succeeded"),
+            false => print_hook(Color::Red, hook, command, &status.to_string()),
+        };
+        outcome.commands.push(json!({
+            "hook": hook.name(),
+            "command": command,
+            "exit_code": status.exit_code(),
+            "status": status.to_string(),
+        }));
+        if !status.success() {
+            outcome.failure.get_or_insert(status);
+            if !is_cleanup {
+                break;
+            }
+        }
+    }
+
+    outcome
+}
+
+/// Print how every combination went, from the final state of its steps and from when they
+/// started and finished.
+///
+/// # Returns
+/// * The combinations along with their status and duration
+fn print_summary(
+    combinations: &[Combination],
+    ranges: &[Range<usize>],
+    states: &[StepState],
+    timings: &[(Option<Instant>, Option<Instant>)],
+) -> Result<serde_json::Value, Error> {
+    let labels = combinations
+        .iter()
+        .map(|combination| label(&combination.values, true))
+        .collect::<Vec<String>>();
+    let width = labels.iter().map(String::len).max().unwrap_or_default();
+    execute!(std::io::stdout(), Print("\nSummary:\n")).map_err(|e| Error::Io(Some(e.into())))?;
+
+    let mut summary = Vec::new();
+    for ((combination, range), label) in combinations.iter().zip(ranges).zip(&labels) {
+        let states = &states[range.clone()];
+        let failure = states.iter().find_map(|state| match state {
+            StepState::Finished(captured) if !captured.success() => Some(captured.status()),
+            _ => None,
+        });
+        let is_finished = states
+            .iter()
+            .all(|state| matches!(state, StepState::Finished(_)));
+        let (color, status) = match (failure, is_finished) {
+            (Some(status), _) => (Color::Red, format!("failed, {}", status)),
+            (None, true) => (Color::Green, "succeeded".to_string()),
+            (None, false) => (Color::DarkGrey, "skipped".to_string()),
+        };
+        // The steps of a combination may run at the same time, so its duration is the span
+        // between the first start and the last finish
+        let first = timings[range.clone()]
+            .iter()
+            .filter_map(|(start, _)| *start)
+            .min();
+        let last = timings[range.clone()]
+            .iter()
+            .filter_map(|(_, end)| *end)
+            .max();
+        let duration = first.zip(last).map(|(first, last)| last - first);
+
+        execute!(
+            std::io::stdout(),
+            SetForegroundColor(color),
+            Print(format!(
+                "  {:width$}  {}{}",
+                label,
+                status,
+                duration.map_or(String::new(), |duration| format!(
+                    " in {}",
+                    WorkflowDuration::new(duration)
+                )),
+                width = width
+            )),
+            ResetColor,
+            Print("\n")
+        )
+        .map_err(|e| Error::Io(Some(e.into())))?;
+
+        let mut data = combination.to_json();
+        data["status"] = json!(match (failure, is_finished) {
+            (Some(_), _) => "failed",
+            (None, true) => "succeeded",
+            (None, false) => "skipped",
+        });
+        data["exit_code"] = json!(match (failure, is_finished) {
+            (Some(status), _) => Some(status.exit_code()),
+            (None, true) => Some(0),
+            (None, false) => None,
+        });
+        data["duration"] = json!(duration.map(|duration| duration.as_secs_f64()));
+        summary.push(data);
     }
+
+    Ok(json!(summary))
+}
+
+/// Execute the rendered steps of the workflow and record the run in the history.
+fn execute_steps(
+    run: &Run,
+    args: &Workflow,
+    combinations: &[Combination],
+    command: &str,
+) -> Result<Output, Error> {
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let shell = args.shell().unwrap_or(&SHELL);
+    let concurrency = run
+        .jobs()
+        .or(args.concurrency())
+        .unwrap_or(NonZeroUsize::MIN);
+    let precedence = &combinations[0].precedence;
+    let cwd = combinations[0].cwd.as_deref();
+    let is_matrix = combinations.len() > 1;
+    let is_multi_step = args.is_multi_step() || is_matrix;
+    // The steps of every combination run in the same schedule, one range after the other
+    let ranges = combinations
+        .iter()
+        .scan(0, |start, combination| {
+            let range = *start..*start + combination.steps.len();
+            *start = range.end;
+            Some(range)
+        })
+        .collect::<Vec<Range<usize>>>();
+    let total = ranges.last().map_or(0, |range| range.end);
+    // Steps running at the same time share the terminal, so their lines are told apart
+    let is_parallel = concurrency.get() > 1 && total > 1;
+    let processes = combinations
+        .iter()
+        .flat_map(|combination| {
+            let (env, is_clean) = environment(args, &combination.env, run.clean_env());
+            combination.steps.iter().map(move |(step, command)| {
+                let timeout = step.timeout().or(args.timeout());
+                let process = Process::shell(shell, command)?
+                    .with_env(env.clone())
+                    .with_clean_env(is_clean)
+                    .with_cwd(combination.cwd.as_deref())
+                    .with_timeout(timeout.map(|timeout| timeout.inner()));
+                Ok(match is_parallel {
+                    true => process.with_prefix(step.name().inner()),
+                    false => process,
+                })
+            })
+        })
+        .collect::<Result<Vec<Process>, Error>>()?;
+    let steps = combinations
+        .iter()
+        .flat_map(|combination| &combination.steps)
+        .map(|(step, _)| step.clone())
+        .collect::<Vec<Step>>();
+
+    let mut failure: Option<ExitStatus> = None;
+    let mut timings = vec![(None, None); total];
+    let attempts = Mutex::new(Vec::new());
+
+    let before = run_hooks(run, args, combinations, Hook::Before);
+    // No step runs once a `before` command failed, but the other hooks still do
+    let states = match before.failure {
+        Some(_) => Ok(steps.iter().map(|_| StepState::Skipped).collect()),
+        None => Scheduler::new(&steps, concurrency).run(
+            |index| {
+                let step = &steps[index];
+                let retry = step.retry().or(args.retry());
+                let allowed = retry.map_or(1, |retry| retry.attempts().get());
+
+                let mut number = 1;
+                loop {
+                    let started = Instant::now();
+                    let captured = processes[index]
+                        .run(run.mode())
+                        .map_err(|e| format!("{:?}", e))?;
+                    attempts
+                        .lock()
+                        .map_err(|e| e.to_string())?
+                        .push(Attempt::new(
+                            step.name().inner(),
+                            number,
+                            captured.status().clone(),
+                            started.elapsed(),
+                        ));
+
+                    let Some(retry) = retry.filter(|_| {
+                        !captured.success()
+                            && number < allowed
+                            && captured.status().interrupted().is_none()
+                    }) else {
+                        return Ok(captured);
+                    };
+
+                    let delay = retry.delay_after(number);
+                    let status = format!(
+                        "attempt {} of {} {}, retrying in {}",
+                        number,
+                        allowed,
+                        captured.status(),
+                        WorkflowDuration::new(delay)
+                    );
+                    print_step(Color::Yellow, index, total, step, &status)
+                        .map_err(|e| format!("{:?}", e))?;
+                    thread::sleep(delay);
+                    number += 1;
+                }
+            },
+            |index, state| {
+                let step = &steps[index];
+                match state {
+                    StepState::Running => timings[index].0 = Some(Instant::now()),
+                    StepState::Finished(_) => timings[index].1 = Some(Instant::now()),
+                    _ => {}
+                }
+                let StepState::Finished(captured) = state else {
+                    return match (is_multi_step, state) {
+                        (true, StepState::Running) => {
+                            print_step(Color::Cyan, index, total, step, "running")
+                        }
+                        (true, StepState::Skipped) => {
+                            print_step(Color::DarkGrey, index, total, step, "skipped")
+                        }
+                        _ => Ok(()),
+                    };
+                };
+
+                let is_stopping =
+                    !step.continue_on_error() || captured.status().interrupted().is_some();
+                if !captured.success() && is_stopping && failure.is_none() {
+                    failure = Some(captured.status().clone());
+                }
+
+                match (is_multi_step, captured.success()) {
+                    (true, true) => print_step(Color::Green, index, total, step, "succeeded"),
+                    (true, false) if !is_stopping => {
+                        let status = format!("{}, continuing", captured.status());
+                        print_step(Color::Yellow, index, total, step, &status)
+                    }
+                    (true, false) => {
+                        let status = captured.status().to_string();
+                        print_step(Color::Red, index, total, step, &status)
+                    }
+                    (false, true) => Ok(()),
+                    (false, false) => execute!(
+                        std::io::stdout(),
+                        SetForegroundColor(Color::Red),
+                        Print(format!("\nThe command {}.\n", captured.status())),
+                        ResetColor
+                    )
+                    .map_err(|e| Error::Io(Some(e.into()))),
+                }
+            },
+        ),
+    };
+
+    let failure = before.failure.clone().or(failure);
+    let on_failure = match failure.is_some() || states.is_err() {
+        true => run_hooks(run, args, combinations, Hook::OnFailure),
+        false => HookOutcome::default(),
+    };
+    let after = run_hooks(run, args, combinations, Hook::After);
+    let states = states?;
+    let hooks = [&before, &on_failure, &after];
+
+    let (mut stdout, mut stderr) = states.iter().fold(
+        (before.stdout.clone(), before.stderr.clone()),
+        |(mut stdout, mut stderr), state| {
+            if let StepState::Finished(captured) = state {
+                stdout.push_str(captured.stdout());
+                stderr.push_str(captured.stderr());
+            }
+            (stdout, stderr)
+        },
+    );
+    for outcome in [&on_failure, &after] {
+        stdout.push_str(&outcome.stdout);
+        stderr.push_str(&outcome.stderr);
+    }
+
+    let mut attempts = attempts
+        .into_inner()
+        .map_err(|e| Error::Io(Some(e.to_string().into())))?;
+    attempts.sort_by_key(|attempt| {
+        let index = steps
+            .iter()
+            .position(|step| step.name().inner() == attempt.step());
+        (index, attempt.number())
+    });
+    print_attempts(&attempts)?;
+
+    let cwd = cwd.map(Path::to_path_buf).or(std::env::current_dir().ok());
+    let mut data = json!({
+        "workflow": args.id().inner(),
+        "arguments": precedence,
+        "command": command,
+        "executed": true,
+        "cwd": cwd,
+        "started_at": started_at,
+        "duration": started.elapsed().as_secs_f64(),
+    });
+    if is_matrix {
+        data["combinations"] = print_summary(combinations, &ranges, &states, &timings)?;
+    }
+    if hooks.iter().any(|outcome| !outcome.commands.is_empty()) {
+        data["hooks"] = json!(hooks
+            .iter()
+            .flat_map(|outcome| &outcome.commands)
+            .collect::<Vec<_>>());
+    }
+    // A failing `after` command fails a run that went well otherwise
+    let output = Output::new("command", command)
+        .with_streams(&stdout, &stderr)
+        .with_status(
+            failure
+                .or(after.failure)
+                .unwrap_or(ExitStatus::new(Some(0), None)),
+        )
+        .with_attempts(attempts)
+        .with_data(data);
+
+    let entry = HistoryEntry::new(
+        args.id(),
+        precedence,
+        command,
+        cwd.as_deref(),
+        started_at,
+        started.elapsed(),
+        output.exit_code(),
+    );
+    remember_values(args, precedence);
+    // The command already ran, so a history that cannot be written must not fail it
+    if let Err(error) = STORE.record(&entry) {
+        eprintln!(
+            "{}Warning: the run could not be recorded in the history: {:?}{}",
+            SetForegroundColor(Color::Yellow),
+            error,
+            ResetColor
+        );
+    }
+
+    Ok(output)
+}
+
+/// The environment variables to run the command of the workflow with, given its `env` and its
+/// `env_policy`, or `clean` if `clean_env` is set.
+///
+/// # Returns
+/// * The variables and whether they replace the inherited ones instead of being added to them
+fn environment(
+    workflow: &Workflow,
+    env: &[(String, String)],
+    clean_env: bool,
+) -> (Vec<(String, String)>, bool) {
+    let policy = match clean_env {
+        true => WorkflowEnvPolicy::Clean,
+        false => workflow.env_policy(),
+    };
+
+    match policy {
+        WorkflowEnvPolicy::Inherit => (env.to_vec(), false),
+        // The allowed variables are set on top of an empty environment
+        WorkflowEnvPolicy::Clean | WorkflowEnvPolicy::Allowlist => (
+            std::env::vars()
+                .filter(|(key, _)| policy.allows(key, workflow.env_allow()))
+                .chain(env.iter().cloned())
+                .collect(),
+            true,
+        ),
+    }
+}
+
+/// What the `if` of the workflow and its steps is evaluated against: the arguments, the
+/// environment along with the `env` of the workflow, and the directory it runs in.
+fn scope<'a>(workflow: &Workflow, precedence: &'a Precedence) -> Result<Scope<'a>, Error> {
+    let env = std::env::vars().chain(workflow.env(precedence)?).collect();
+    let cwd = workflow
+        .cwd(precedence)?
+        .or(std::env::current_dir().ok())
+        .unwrap_or_default();
+
+    Ok(Scope::new(precedence, env, &cwd))
+}
+
+/// Split the steps of the workflow, in order, into the ones to run and the ones whose `if` does
+/// not hold. The steps needing a skipped step still run.
+fn split_steps(workflow: &Workflow, scope: &Scope) -> Result<(Vec<Step>, Vec<Step>), Error> {
+    let mut steps = Vec::new();
+    let mut skipped = Vec::new();
+    for step in workflow.steps() {
+        match step.condition() {
+            Some(condition) if !condition.evaluate(scope)? => skipped.push(step),
+            _ => steps.push(step),
+        }
+    }
+
+    Ok((steps, skipped))
+}
+
+/// The command of the steps run one after the other, e.g. a stage of a pipeline.
+fn stage_command(
+    steps: &[Step],
+    precedence: &Precedence,
+    vars: &BTreeMap<String, String>,
+    shell: &WorkflowShell,
+) -> Result<String, Error> {
+    Ok(steps
+        .iter()
+        .map(|step| step.command().replace(precedence, vars, shell))
+        .collect::<Result<Vec<String>, Error>>()?
+        .join("\n"))
+}
+
+/// The stand-ins of the variables of the workflow until they are computed, e.g. `<vars.sha>`.
+fn pending_vars(workflow: &Workflow) -> BTreeMap<String, String> {
+    workflow
+        .vars()
+        .iter()
+        .map(|var| (var.name().to_string(), format!("<vars.{}>", var.name())))
+        .collect()
+}
+
+/// The command of every variable of the workflow, along with its name, rendered with the
+/// stand-ins of the variables.
+fn var_commands(
+    workflow: &Workflow,
+    precedence: &Precedence,
+    shell: &WorkflowShell,
+) -> Result<Vec<(String, String)>, Error> {
+    let vars = pending_vars(workflow);
+
+    workflow
+        .vars()
+        .iter()
+        .map(|var| {
+            let command = var.command().replace(precedence, &vars, shell)?;
+            Ok((var.name().to_string(), command))
+        })
+        .collect()
+}
+
+/// Run the command of every variable of the workflow, in order, with the environment and in the
+/// directory of the workflow, and extract the value of the variable from its output.
+///
+/// # Returns
+/// * The value of every variable or an `Error` if a command failed or printed something else
+fn compute_vars(
+    workflow: &Workflow,
+    precedence: &Precedence,
+    clean_env: bool,
+) -> Result<BTreeMap<String, String>, Error> {
+    if workflow.vars().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let cwd = workflow.cwd(precedence)?;
+    check_cwd(workflow, cwd.as_deref())?;
+    let env = workflow.env(precedence)?;
+    let (env, is_clean) = environment(workflow, &env, clean_env);
+    let shell = workflow.shell().unwrap_or(&SHELL);
+
+    workflow
+        .vars()
+        .iter()
+        .try_fold(BTreeMap::new(), |mut vars, var| {
+            let command = var.command().replace(precedence, &vars, shell)?;
+            let captured = Process::shell(shell, &command)?
+                .with_env(env.clone())
+                .with_clean_env(is_clean)
+                .with_cwd(cwd.as_deref())
+                .capture()?;
+
+            if !captured.success() {
+                let message = format!(
+                    "The command of variable `{}` {}",
+                    var.name(),
+                    captured.status()
+                );
+                return Err(Error::InvalidCommand(Some(
+                    match captured.stderr().trim() {
+                        "" => message,
+                        stderr => format!("{}: {}", message, stderr),
+                    }
+                    .into(),
+                )));
+            }
+
+            vars.insert(var.name().to_string(), var.extract(captured.stdout())?);
+            Ok(vars)
+        })
+}
+
+/// Check that the directory the workflow runs in exists, so that a typo is not mistaken for a
+/// failure of the command.
+fn check_cwd(workflow: &Workflow, cwd: Option<&Path>) -> Result<Unit, Error> {
+    match cwd.filter(|cwd| !cwd.is_dir()) {
+        Some(cwd) => Err(Error::ReadError(Some(
+            format!(
+                "The working directory `{}` of workflow `{}` does not exist",
+                cwd.display(),
+                workflow.name().inner()
+            )
+            .into(),
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ask whether to execute the command of the workflow with the given name, according to its
+/// confirm policy. A command that looks dangerous is never executed without someone agreeing
+/// to it, by answering the prompt or with `--yes`, and neither is a command to confirm when
+/// there is no terminal to prompt in.
+///
+/// # Returns
+/// * Whether the command is confirmed or an `Error` if it has to be confirmed without a terminal
+fn confirm(
+    policy: WorkflowConfirm,
+    name: &str,
+    is_dangerous: bool,
+    yes: bool,
+    is_interactive: bool,
+) -> Result<bool, Error> {
+    Ok(match policy {
+        _ if yes => true,
+        _ if is_dangerous && !is_interactive => {
+            return Err(Error::InvalidArguments(Some(
+                format!(
+                    "The command of workflow `{}` looks dangerous, pass `--yes` to execute it without a terminal",
+                    name
+                )
+                .into(),
+            )));
+        }
+        _ if is_dangerous => {
+            // stdout may be the output of a pipeline or the JSON document
+            eprintln!(
+                "{}The command looks dangerous, check the highlighted parts.{}",
+                SetForegroundColor(Color::Red),
+                ResetColor
+            );
+            Text::new("Type `yes` to execute it:")
+                .prompt()
+                .map_err(|e| Error::ReadError(Some(e.into())))?
+                .trim()
+                == "yes"
+        }
+        WorkflowConfirm::Never | WorkflowConfirm::Dangerous => true,
+        WorkflowConfirm::Always if is_interactive => {
+            Confirm::new("Do you want to execute the command?")
+                .prompt()
+                .map_err(|e| Error::ReadError(Some(e.into())))?
+        }
+        // Scripts cannot answer the prompt, so they have to agree up front
+        WorkflowConfirm::Always => {
+            return Err(Error::InvalidArguments(Some(
+                format!(
+                    "The command of workflow `{}` has to be confirmed, pass `--yes` to execute it without a terminal",
+                    name
+                )
+                .into(),
+            )));
+        }
+    })
+}
+
+/// The globs of the files whose changes run the workflow again, the ones of `--watch` or else
+/// the `watch` key of the workflow. The key is only followed by an interactive run, so that
+/// scripts, jobs and reruns end.
+fn watch_patterns(
+    run: &Run,
+    workflow: &Workflow,
+    precedence: &Precedence,
+    is_interactive: bool,
+) -> Result<Vec<String>, Error> {
+    match run.watch().is_empty() {
+        true if run.watch_key() && is_interactive => workflow.watch(precedence),
+        true => Ok(Vec::new()),
+        false => Ok(run.watch().to_vec()),
+    }
+}
+
+/// Run the workflow with `run`, then again every time a file matching one of the globs
+/// changes, cancelling the previous run if it is still going, until `workflow` is interrupted.
+///
+/// # Returns
+/// * The `Output` of the last run or an `Error` if the files cannot be watched
+fn watch<R>(patterns: &[String], cwd: Option<&Path>, run: R) -> Result<Output, Error>
+where
+    R: Fn() -> Result<Output, Error>,
+{
+    let root = cwd
+        .map(Path::to_path_buf)
+        .or(std::env::current_dir().ok())
+        .unwrap_or_default();
+    let watcher = Watcher::new(&root, patterns)?;
+    let patterns = patterns
+        .iter()
+        .map(|pattern| format!("`{}`", pattern))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let is_stopped = AtomicBool::new(false);
+    let (sender, changes) = mpsc::channel();
+
+    thread::scope(|scope| {
+        // Watches the files while the runs happen here, stopping the current one on a change
+        let is_stopped = &is_stopped;
+        scope.spawn(move || {
+            while !is_stopped.load(AtomicOrdering::SeqCst) {
+                if let Some(changed) = watcher.wait(WATCH_INTERVAL) {
+                    cancel();
+                    if sender.send(changed).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let output = loop {
+            resume();
+            let output = run();
+            if interrupted().is_some() {
+                break output;
+            }
+            // A run that could not even start is worth running again once the files are fixed
+            if let Err(error) = &output {
+                eprintln!(
+                    "{}{:?}{}",
+                    SetForegroundColor(Color::Red),
+                    error,
+                    ResetColor
+                );
+            }
+
+            let mut is_waiting = false;
+            let changed = loop {
+                if interrupted().is_some() {
+                    break None;
+                }
+                if let Ok(changed) = changes.recv_timeout(WATCH_INTERVAL) {
+                    break Some(changed);
+                }
+                if !is_waiting {
+                    execute!(
+                        std::io::stdout(),
+                        SetForegroundColor(Color::Cyan),
+                        Print(format!(
+                            "\nWatching {} for changes, press Ctrl-C to stop\n",
+                            patterns
+                        )),
+                        ResetColor
+                    )
+                    .map_err(|e| Error::Io(Some(e.into())))?;
+                    is_waiting = true;
+                }
+            };
+
+            let Some(changed) = changed else {
+                break output;
+            };
+            execute!(
+                std::io::stdout(),
+                SetForegroundColor(Color::Yellow),
+                Print(format!(
+                    "\n{} changed, running the workflow again\n\n",
+                    changed
+                        .iter()
+                        .map(|path| format!(
+                            "`{}`",
+                            path.strip_prefix(&root).unwrap_or(path).display()
+                        ))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )),
+                ResetColor
+            )
+            .map_err(|e| Error::Io(Some(e.into())))?;
+        };
+
+        is_stopped.store(true, AtomicOrdering::SeqCst);
+        output
+    })
+}
+
+/// Start the given run of a workflow in a detached process, which runs it with `workflow run-job`
+/// while its output is written to the directory of the job.
+fn detach(
+    run: &Run,
+    workflow: &Workflow,
+    precedence: &Precedence,
+    command: &str,
+) -> Result<Output, Error> {
+    let job = start_job(&STORE, run, workflow, precedence, command)?;
+    let id = job.id();
+    let directory = job_dir(id);
+
+    println!(
+        "Started job {}, see its output with `workflow logs {} --follow`",
+        id, id
+    );
+    let mut document = job.to_json(JobStatus::Running);
+    document["stdout_log"] = json!(directory.join(STDOUT_LOG));
+    document["stderr_log"] = json!(directory.join(STDERR_LOG));
+    Ok(Output::new("job", &id.to_string()).with_data(document))
+}
+
+/// Start the workflows with a schedule due after `checked_at` and up to `now`, as jobs. Once a
+/// job is done, its outcome is sent with `sender`.
+///
+/// # Returns
+/// * The next time a schedule is due, if any, or an `Error` if the workflows cannot be read
+fn run_due_schedules(
+    store: &WorkStore,
+    checked_at: &DateTime<Local>,
+    now: &DateTime<Local>,
+    sender: &Sender<ScheduledRun>,
+) -> Result<Option<DateTime<Local>>, Error> {
+    // Workflows added or changed since the last check are scheduled too
+    Crawler::crawl(&WORKDIR, store)?;
+    let workflows = store.get_all()?;
+
+    for workflow in &workflows {
+        for schedule in workflow.schedule() {
+            if schedule
+                .cron()
+                .next_after(checked_at)
+                .is_some_and(|due| due <= *now)
+            {
+                if let Err(error) = start_scheduled_run(store, workflow, schedule, sender.clone()) {
+                    print_warning(&format!(
+                        "The workflow `{}` scheduled at `{}` could not be started: {:?}",
+                        workflow.name().inner(),
+                        schedule.cron(),
+                        error
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(workflows
+        .iter()
+        .flat_map(|workflow| workflow.schedule())
+        .filter_map(|schedule| schedule.cron().next_after(now))
+        .min())
+}
+
+/// Start the workflow as a job with the argument values of the schedule, the other arguments
+/// falling back to their default value.
+fn start_scheduled_run(
+    store: &WorkStore,
+    workflow: &Workflow,
+    schedule: &WorkflowSchedule,
+    sender: Sender<ScheduledRun>,
+) -> Result<Unit, Error> {
+    let provided = schedule.arguments().clone().into_iter().collect();
+    let precedence = resolve_arguments(workflow, provided, false)?;
+    // Only for the job to show, the variables are computed by the job alone once it runs
+    let command = stage_command(
+        &workflow.steps(),
+        &precedence,
+        &pending_vars(workflow),
+        workflow.shell().unwrap_or(&SHELL),
+    )?;
+    let run = Run::new(workflow.name().inner());
+    let job = start_job(store, &run, workflow, &precedence, &command)?;
+
+    println!(
+        "{}  Started job {} running `{}`, scheduled at `{}`",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        job.id(),
+        workflow.id().inner(),
+        schedule.cron()
+    );
+
+    let id = workflow.id();
+    let schedule = schedule.clone();
+    thread::spawn(move || {
+        let started = Instant::now();
+        let status = job.pid().map(wait_detached);
+        // The job writes the exit code of the workflow, unless it could not even start it
+        let code = exit_code(&job).or_else(|| match status {
+            Some(Ok(status)) => Some(status.exit_code()),
+            Some(Err(_)) | None => None,
+        });
+        let run = ScheduledRun::new(
+            id,
+            schedule,
+            &precedence,
+            job.id(),
+            job.started_at(),
+            started.elapsed(),
+            code.unwrap_or(1),
+            &job_dir(job.id()),
+        );
+        let _ = sender.send(run);
+    });
+
+    Ok(())
+}
+
+/// Record the outcome of the scheduled runs that are done, keeping the ones that could not be
+/// recorded for later.
+fn record_scheduled_runs(store: &WorkStore, done: &mut Vec<ScheduledRun>) {
+    done.retain(|run| {
+        println!(
+            "{}  Job {} running `{}` {}exited with code {}{} after {:.1}s",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            run.job(),
+            run.workflow().inner(),
+            SetForegroundColor(match run.success() {
+                true => Color::Green,
+                false => Color::Red,
+            }),
+            run.exit_code(),
+            ResetColor,
+            run.duration().as_secs_f64()
+        );
+
+        match store.record_scheduled_run(run) {
+            Ok(()) => false,
+            Err(error) => {
+                print_warning(&format!(
+                    "The run of job {} could not be recorded, trying again in a moment: {:?}",
+                    run.job(),
+                    error
+                ));
+                true
+            }
+        }
+    });
+}
+
+/// Print a warning about something that went wrong without stopping `workflow scheduler`.
+fn print_warning(message: &str) {
+    eprintln!(
+        "{}Warning: {}{}",
+        SetForegroundColor(Color::Yellow),
+        message,
+        ResetColor
+    );
+}
+
+/// List the schedules of every workflow with when they are due next and how they last ended.
+fn list_schedules(store: &WorkStore) -> Result<Output, Error> {
+    let now = Local::now();
+    let runs = store.scheduled_runs()?;
+    let schedules = store
+        .get_all()?
+        .into_iter()
+        .flat_map(|workflow| {
+            workflow
+                .schedule()
+                .iter()
+                .map(|schedule| (workflow.id(), schedule.clone()))
+                .collect::<Vec<(WorkflowId, WorkflowSchedule)>>()
+        })
+        .map(|(workflow, schedule)| {
+            let last = runs
+                .iter()
+                .find(|run| run.workflow() == &workflow && run.schedule() == &schedule);
+            let next = schedule.cron().next_after(&now);
+            (workflow, schedule, next, last)
+        })
+        .collect::<Vec<_>>();
+
+    let lines = schedules
+        .iter()
+        .map(|(workflow, schedule, next, last)| {
+            let arguments = schedule
+                .arguments()
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let last = match last {
+                Some(run) => format!(
+                    "{}{}{} exited with code {} after {:.1}s (job {})",
+                    SetForegroundColor(match run.success() {
+                        true => Color::Green,
+                        false => Color::Red,
+                    }),
+                    run.started_at()
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    ResetColor,
+                    run.exit_code(),
+                    run.duration().as_secs_f64(),
+                    run.job()
+                ),
+                None => "never".to_string(),
+            };
+
+            format!(
+                "{}{}{}  {}  {}\n      next {}, last {}",
+                SetForegroundColor(Color::White),
+                workflow.inner(),
+                ResetColor,
+                schedule.cron(),
+                arguments,
+                next.map_or("never".to_string(), |next| next
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()),
+                last
+            )
+        })
+        .collect::<Vec<String>>();
+
+    if lines.is_empty() {
+        println!("No schedules found");
+    } else {
+        println!("{}", lines.join("\n"));
+    }
+
+    let schedules = schedules
+        .iter()
+        .map(|(workflow, schedule, next, last)| {
+            json!({
+                "workflow": workflow.inner(),
+                "cron": schedule.cron().inner(),
+                "arguments": schedule.arguments(),
+                "next": next,
+                "last": last.map(ScheduledRun::to_json),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Output::new("schedules", "success").with_data(json!({ "schedules": schedules })))
+}
+
+/// Start a process running the workflow in the background as a new job, see `RunJob`.
+///
+/// # Returns
+/// * The started `Job` or an `Error` if it could not be written or started
+fn start_job(
+    store: &WorkStore,
+    run: &Run,
+    workflow: &Workflow,
+    precedence: &Precedence,
+    command: &str,
+) -> Result<Job, Error> {
+    let first = store.jobs()?.last().map_or(1, |job| job.id() + 1);
+    let (id, directory) = create_job_dir(&WORKDIR.join(JOBS_DIR), first)?;
+    let job = Job::new(id, workflow.clone(), precedence, command, Utc::now());
+    let create = |name: &str| {
+        File::create(directory.join(name)).map_err(|e| Error::WriteError(Some(e.into())))
+    };
+
+    let content = serde_json::to_string(&job).map_err(|e| Error::WriteError(Some(e.into())))?;
+    fs::write(directory.join(JOB_FILE), content).map_err(|e| Error::WriteError(Some(e.into())))?;
+
+    let program = std::env::current_exe().map_err(|e| Error::Io(Some(e.into())))?;
+    let args = RunJob::new(run, id).to_args();
+    let pid = Process::new(&program.to_string_lossy(), args)
+        .spawn_detached(create(STDOUT_LOG)?, create(STDERR_LOG)?)?;
+    let job = job.with_pid(pid);
+    store.save_job(&job)?;
+
+    Ok(job)
+}
+
+/// The directory holding the output of the job with the given number.
+fn job_dir(id: u64) -> PathBuf {
+    WORKDIR.join(JOBS_DIR).join(id.to_string())
+}
+
+/// Create the directory of a new job within the given one, with the first number from `first`
+/// that no other job has. Creating it fails for a number already taken, even by a job started
+/// meanwhile.
+fn create_job_dir(jobs: &Path, first: u64) -> Result<(u64, PathBuf), Error> {
+    fs::create_dir_all(jobs).map_err(|e| Error::WriteError(Some(e.into())))?;
+
+    let mut id = first;
+    loop {
+        let directory = jobs.join(id.to_string());
+        match fs::create_dir(&directory) {
+            Ok(()) => return Ok((id, directory)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => id += 1,
+            Err(e) => return Err(Error::WriteError(Some(e.into()))),
+        }
+    }
+}
+
+fn find_job(id: u64) -> Result<Job, Error> {
+    STORE.job(id)?.ok_or(Error::InvalidArguments(Some(
+        format!("There is no job {}, see `workflow jobs`", id).into(),
+    )))
+}
+
+/// The code the detached process exited with, once it is done.
+fn exit_code(job: &Job) -> Option<i32> {
+    fs::read_to_string(job_dir(job.id()).join(EXIT_CODE_FILE))
+        .ok()
+        .and_then(|code| code.trim().parse().ok())
+}
+
+fn job_status(job: &Job) -> JobStatus {
+    if job.pid().is_some_and(is_running) {
+        return JobStatus::Running;
+    }
+
+    // The exit code is written right before the process is done, so it is read afterwards
+    match exit_code(job) {
+        Some(code) => JobStatus::Exited(code),
+        None => JobStatus::Lost,
+    }
+}
+
+/// Point stdout at stderr, so that whatever is printed or run from now on ends up there, and get
+/// a writer for the original stdout, which is left to the document of `--output json`.
+pub fn reserve_stdout() -> Result<Box<dyn Write>, Error> {
+    io::stdout()
+        .flush()
+        .map_err(|e| Error::Io(Some(e.into())))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+
+        // SAFETY: duplicating the standard descriptors does not touch any memory. The copy of
+        // stdout is closed on exec, so that commands run later cannot keep it open.
+        let fd = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 3) };
+        if fd == -1 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } == -1 {
+            return Err(Error::Io(Some(io::Error::last_os_error().into())));
+        }
+
+        // SAFETY: the descriptor was just duplicated, so nothing else owns it
+        Ok(Box::new(unsafe { File::from_raw_fd(fd) }))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(Box::new(io::stdout()))
+    }
+}
+
+/// A writer for the given file descriptor, e.g. `3` for the shell integration to capture
+/// the rendered command while stdout is still the terminal.
+fn printer(fd: i32) -> Result<Box<dyn Write>, Error> {
+    match fd {
+        1 => Ok(Box::new(io::stdout())),
+        2 => Ok(Box::new(io::stderr())),
+        #[cfg(unix)]
+        fd if fd > 2 => {
+            use std::os::fd::FromRawFd;
+
+            // SAFETY: `F_GETFD` only queries the flags of the descriptor
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                return Err(Error::Io(Some(
+                    format!("The file descriptor {} is not open", fd).into(),
+                )));
+            }
+
+            // SAFETY: the descriptor is open and owned by nothing else in this process
+            Ok(Box::new(unsafe { std::fs::File::from_raw_fd(fd) }))
+        }
+        fd => Err(Error::InvalidArguments(Some(
+            format!("Unable to print to the file descriptor {}", fd).into(),
+        ))),
+    }
+}
+
+/// Print the status of a command of a hook, e.g. `[before] ./start-db.sh: succeeded`.
+fn print_hook(color: Color, hook: Hook, command: &str, status: &str) -> Result<Unit, Error> {
+    execute!(
+        std::io::stdout(),
+        SetForegroundColor(color),
+        Print(format!("[{}] {}: {}", hook.name(), command, status)),
+        ResetColor,
+        Print("\n")
+    )
+    .map_err(|e| Error::Io(Some(e.into())))
+}
+
+/// Print the status of a step of a multi-step workflow, e.g. `[2/3] build: succeeded`.
+fn print_step(
+    color: Color,
+    index: usize,
+    total: usize,
+    step: &Step,
+    status: &str,
+) -> Result<Unit, Error> {
+    execute!(
+        std::io::stdout(),
+        SetForegroundColor(color),
+        Print(format!(
+            "[{}/{}] {}: {}",
+            index + 1,
+            total,
+            step.name().inner(),
+            status
+        )),
+        ResetColor,
+        Print("\n")
+    )
+    .map_err(|e| Error::Io(Some(e.into())))
+}
+
+/// Highlight the given parts of a command, within a preview printed in green.
+fn highlight(command: &str, parts: &[Range<usize>]) -> String {
+    let mut text = String::new();
+    let mut end = 0;
+    for part in parts {
+        text.push_str(&command[end..part.start]);
+        text.push_str(&format!(
+            "{}{}{}{}{}",
+            SetForegroundColor(Color::Red),
+            SetAttribute(Attribute::Bold),
+            &command[part.clone()],
+            SetAttribute(Attribute::NormalIntensity),
+            SetForegroundColor(Color::Green),
+        ));
+        end = part.end;
+    }
+    text.push_str(&command[end..]);
+
+    text
+}
+
+/// Remember the values given to the arguments of the workflow, to suggest them in later runs.
+/// Arguments that opted out and placeholders of arguments left without a value are skipped.
+fn remember_values(workflow: &Workflow, precedence: &Precedence) {
+    let values = workflow
+        .all_arguments()
+        .into_iter()
+        .filter(|argument| argument.remember())
+        .filter_map(|argument| {
+            let name = argument.name().inner();
+            precedence
+                .get(name)
+                .filter(|value| value.as_str() != PLACEHOLDER)
+                .map(|value| (name.to_owned(), value.to_owned()))
+        })
+        .collect::<Precedence>();
+
+    if let Err(error) = STORE.remember(&workflow.id(), &values) {
+        eprintln!(
+            "{}Warning: the argument values could not be remembered: {:?}{}",
+            SetForegroundColor(Color::Yellow),
+            error,
+            ResetColor
+        );
+    }
+}
+
+/// Summarize the attempts of the steps that were retried or timed out.
+fn print_attempts(attempts: &[Attempt]) -> Result<Unit, Error> {
+    let notable = attempts
+        .iter()
+        .filter(|attempt| {
+            attempt.status().is_timed_out()
+                || attempts
+                    .iter()
+                    .any(|other| other.step() == attempt.step() && other.number() > 1)
+        })
+        .collect::<Vec<&Attempt>>();
+
+    if notable.is_empty() {
+        return Ok(());
+    }
+
+    let summary = notable
+        .iter()
+        .map(|attempt| format!("  {}: {}", attempt.step(), attempt))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    execute!(
+        std::io::stdout(),
+        Print(format!("\nAttempts:\n{}\n", summary))
+    )
+    .map_err(|e| Error::Io(Some(e.into())))
 }
 
 fn non_empty_filter<T: ToString>(value: &T) -> bool {
     !value.to_string().trim().is_empty()
 }
 
+/// The names of the arguments used by the placeholders of the command, e.g. `name` for
+/// `{{name}}`, `{{quote name}}` or `{{join (dirname path) name}}`, leaving out helpers,
+/// keywords, literals, hashes and variables.
 fn get_values(command: &str) -> Result<HashSet<&str>, Error> {
     let values = command
         .split("{{")
         .skip(1)
-        .filter_map(|part| part.split("}}").next().map(|inner_part| inner_part.trim()))
-        .filter(|part| !part.is_empty())
+        .filter_map(|part| part.split("}}").next())
+        .flat_map(|inner_part| {
+            inner_part
+                .trim_start_matches(['{', '#', '/', '~'])
+                .trim_end_matches(['}', '~'])
+                .split_whitespace()
+                .map(|part| part.trim_matches(['(', ')']))
+        })
+        .filter(|part| {
+            !part.is_empty()
+                && !TEMPLATE_HELPERS.contains(part)
+                && !SCRIPT_HELPERS.names().any(|name| name == *part)
+                && !["if", "unless", "each", "with", "else", "this"].contains(part)
+                && !part.starts_with(['"', '\''])
+                && !part.ends_with(['"', '\''])
+                && !part.contains('=')
+                && !part.starts_with("vars.")
+                && part.parse::<f64>().is_err()
+        })
         .collect();
 
     Ok(values)
@@ -465,6 +2818,101 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_execute_init() {
+        let command = Init::new(InitShell::Bash);
+
+        let result = command.execute(()).unwrap();
+
+        assert_eq!(result.r#type(), "init");
+        assert_eq!(result.message(), "bash");
+    }
+
+    #[test]
+    fn test_get_values() {
+        let values = get_values(
+            "git tag {{quote version}} {{#if sign}}-s{{/if}} -m {{raw message}} {{vars.sha}} {{version}} \
+            {{join (dirname path) (default name 'a b') sep='-'}} {{now '%Y %m'}}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            values,
+            HashSet::from(["version", "sign", "message", "path", "name"])
+        );
+    }
+
+    #[test]
+    fn test_confirm_never() {
+        assert!(confirm(WorkflowConfirm::Never, "clean", false, false, true).unwrap());
+        assert!(confirm(WorkflowConfirm::Never, "clean", false, false, false).unwrap());
+        assert!(confirm(WorkflowConfirm::Never, "clean", true, true, false).unwrap());
+        // A dangerous command still needs someone to agree to it
+        assert!(confirm(WorkflowConfirm::Never, "clean", true, false, false).is_err());
+    }
+
+    #[test]
+    fn test_confirm_without_terminal() {
+        assert!(confirm(WorkflowConfirm::Always, "build", false, false, false).is_err());
+        assert!(confirm(WorkflowConfirm::Always, "build", false, true, false).unwrap());
+        assert!(confirm(WorkflowConfirm::Dangerous, "build", false, false, false).unwrap());
+        assert!(confirm(WorkflowConfirm::Dangerous, "build", true, false, false).is_err());
+    }
+
+    #[test]
+    fn test_watch_patterns() {
+        let workflow = r#"
+            name: test
+            command: cargo test
+            watch:
+              - "src/**/*.rs"
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let arguments = Precedence::new();
+        let run = Run::new("test");
+
+        assert_eq!(
+            watch_patterns(&run, &workflow, &arguments, true).unwrap(),
+            ["src/**/*.rs"]
+        );
+        // Scripts and jobs would never end
+        assert!(watch_patterns(&run, &workflow, &arguments, false)
+            .unwrap()
+            .is_empty());
+        assert!(
+            watch_patterns(&run.with_watch_key(false), &workflow, &arguments, true)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_create_job_dir() {
+        let jobs = std::env::temp_dir().join("workflow_test_create_job_dir");
+        fs::remove_dir_all(&jobs).unwrap_or_default();
+
+        // Jobs started at once all see the same last job
+        let mut ids = (0..8)
+            .map(|_| {
+                let jobs = jobs.clone();
+                thread::spawn(move || create_job_dir(&jobs, 1).unwrap().0)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<u64>>();
+        ids.sort();
+        fs::remove_dir_all(&jobs).unwrap_or_default();
+
+        assert_eq!(ids, (1..=8).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_printer_with_invalid_descriptor() {
+        assert!(printer(1).is_ok());
+        assert!(printer(-1).is_err());
+    }
+
     #[test]
     fn test_execute_list() {
         set_env_var();
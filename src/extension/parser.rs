@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Read},
+};
 
-use inquire::{required, Text};
+use inquire::{required, MultiSelect, Text};
+use serde_json::Value;
 
 use crate::{
     domain::{
         args::{Argument, ArgumentDefault},
+        command::{Pipe, Run},
         error::Error,
         workflow::Workflow,
     },
-    prelude::Unit,
+    prelude::{RecentValueStore, Unit, STORE},
 };
 
 pub trait Parser {
@@ -29,47 +34,276 @@ pub trait Parser {
 
 pub type Precedence = HashMap<String, String>;
 
-impl Parser for Workflow {
+/// The value of an argument left without any value nor default.
+pub const PLACEHOLDER: &str = "<insert value>";
+
+impl Parser for Run {
     type Error = Error;
     type Output = Precedence;
     type Args = Unit;
 
+    /// Collect the argument values given on the command line. Values passed with
+    /// `--arg` take precedence over the ones read with `--args-json`.
     fn try_parse(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
-        let precedence = self.arguments().iter().try_fold(
-            HashMap::new(),
-            |mut acc, argument| -> Result<HashMap<String, String>, Error> {
-                let name = argument.name().inner().to_string();
-                let suggester = self.clone();
-                let value = if !argument.values().is_empty() {
-                    Text::new(argument.name().inner())
-                        .with_validator(required!("This field is required"))
-                        .with_help_message(argument.def_description())
-                        .with_autocomplete(move |i: &str| suggester.suggestion(i, name.as_str()))
-                        .prompt()
-                        .map_err(|e| Error::ReadError(Some(e.into())))?
-                } else {
-                    Text::new(argument.name().inner())
-                        .with_help_message(argument.def_description())
-                        .prompt()
-                        .map_err(|e| Error::ReadError(Some(e.into())))?
-                };
-
-                if !value.is_empty() {
-                    acc.insert(argument.name().inner().to_string(), value);
-                }
-                Ok(acc)
-            },
-        )?;
+        read_arguments(self.arguments(), self.arguments_json())
+    }
+}
 
-        let mut arguments = HashMap::new();
-        self.arguments().iter().for_each(|arg| {
-            if let Ok(Some(args)) = arg.try_parse(Some(precedence.clone())) {
-                arguments.extend(args);
+impl Parser for Pipe {
+    type Error = Error;
+    type Output = Precedence;
+    type Args = Unit;
+
+    /// Collect the argument values given on the command line, as `Run` does. The stdin of
+    /// `workflow` is the one of the first workflow, so `--args-json -` is not supported.
+    fn try_parse(&self, _: Self::Args) -> Result<Self::Output, Self::Error> {
+        if self.arguments_json() == Some("-") {
+            return Err(Error::InvalidArguments(Some(
+                "`--args-json -` cannot be used with `workflow pipe`, stdin is the input of the first workflow".into(),
+            )));
+        }
+
+        read_arguments(self.arguments(), self.arguments_json())
+    }
+}
+
+/// The values of `--arg`, on top of the ones of the JSON object read from the file of
+/// `--args-json`, or from stdin with `-`.
+fn read_arguments(
+    arguments: &[(String, String)],
+    arguments_json: Option<&str>,
+) -> Result<Precedence, Error> {
+    let mut precedence = match arguments_json {
+        Some("-") => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| Error::ReadError(Some(e.into())))?;
+            parse_json_arguments(&input)?
+        }
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| Error::ReadError(Some(e.into())))
+            .and_then(|input| parse_json_arguments(&input))?,
+        None => HashMap::new(),
+    };
+
+    precedence.extend(arguments.iter().cloned());
+
+    Ok(precedence)
+}
+
+impl Parser for Workflow {
+    type Error = Error;
+    type Output = Precedence;
+    type Args = Precedence;
+
+    /// Resolve the value of every argument of the workflow and its steps. Values already present in the
+    /// given `Precedence` are used as is, the rest are prompted for, suggesting the values last
+    /// given to them. When stdin is not a TTY nothing is prompted and arguments without a default
+    /// value are reported as missing.
+    fn try_parse(&self, provided: Self::Args) -> Result<Self::Output, Self::Error> {
+        resolve_arguments(self, provided, std::io::stdin().is_terminal())
+    }
+}
+
+/// Resolve the value of every argument of the workflow and its steps, as `Workflow::try_parse`
+/// does, prompting for the missing ones only if `is_interactive`.
+pub fn resolve_arguments(
+    workflow: &Workflow,
+    provided: Precedence,
+    is_interactive: bool,
+) -> Result<Precedence, Error> {
+    let unknown = provided
+        .keys()
+        .filter(|key| {
+            !workflow
+                .all_arguments()
+                .iter()
+                .any(|argument| argument.name().inner() == key.as_str())
+        })
+        .map(|key| format!("`{}`", key))
+        .collect::<Vec<String>>();
+
+    if !unknown.is_empty() {
+        return Err(Error::InvalidArguments(Some(
+            format!("Unknown arguments: {}", unknown.join(", ")).into(),
+        )));
+    }
+
+    let missing = workflow
+        .all_arguments()
+        .into_iter()
+        .filter(|argument| !provided.contains_key(argument.name().inner()))
+        .collect::<Vec<&Argument>>();
+
+    if !is_interactive {
+        let unresolved = missing
+            .iter()
+            .filter(|argument| argument.default().is_none())
+            .map(|argument| format!("`{}`", argument.name().inner()))
+            .collect::<Vec<String>>();
+
+        if !unresolved.is_empty() {
+            return Err(Error::InvalidArguments(Some(
+                format!(
+                    "Missing values for arguments {} and stdin is not a TTY, use `--arg <KEY=VALUE>` to provide them",
+                    unresolved.join(", ")
+                )
+                .into(),
+            )));
+        }
+    }
+
+    let precedence = missing.into_iter().filter(|_| is_interactive).try_fold(
+        provided,
+        |mut acc, argument| -> Result<HashMap<String, String>, Error> {
+            let name = argument.name().inner().to_string();
+            let suggester = workflow.clone();
+            // The last value given to the argument is offered as its default
+            let recent = match argument.remember() {
+                true => STORE.recent_values(&workflow.id(), &name)?,
+                false => Vec::new(),
+            };
+            let default = recent.first().cloned();
+
+            let mut prompt =
+                Text::new(argument.name().inner()).with_help_message(argument.def_description());
+            if let Some(default) = &default {
+                prompt = prompt.with_default(default);
+            }
+            if !argument.values().is_empty() {
+                prompt = prompt.with_validator(required!("This field is required"));
+            }
+            if !argument.values().is_empty() || !recent.is_empty() {
+                prompt = prompt.with_autocomplete(move |i: &str| {
+                    suggester.suggestion(i, name.as_str(), &recent)
+                });
             }
-        });
 
-        Ok(arguments)
+            let value = prompt
+                .prompt()
+                .map_err(|e| Error::ReadError(Some(e.into())))?;
+
+            if !value.is_empty() {
+                acc.insert(argument.name().inner().to_string(), value);
+            }
+            Ok(acc)
+        },
+    )?;
+
+    let mut arguments = HashMap::new();
+    workflow.all_arguments().into_iter().for_each(|arg| {
+        if let Ok(Some(args)) = arg.try_parse(Some(precedence.clone())) {
+            arguments.extend(args);
+        }
+    });
+
+    Ok(arguments)
+}
+
+/// Collect the values of the arguments declared with `repeat` and of the `matrix` of the
+/// workflow, in this order. Values given on the command line replace the ones of the matrix.
+/// Repeated arguments without any value are prompted for if `is_interactive`, and otherwise left
+/// to `resolve_arguments`, which uses their default value.
+pub fn resolve_repeated(
+    workflow: &Workflow,
+    provided: &[(String, String)],
+    is_interactive: bool,
+) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let given = |name: &str| {
+        provided
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .fold(Vec::new(), |mut values, value| {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+                values
+            })
+    };
+
+    let mut repeated = Vec::new();
+    for argument in workflow.all_arguments() {
+        let name = argument.name().inner();
+        if !argument.repeat() || workflow.matrix().contains_key(name) {
+            continue;
+        }
+
+        let values = match given(name) {
+            values if !values.is_empty() || !is_interactive => values,
+            _ => prompt_values(workflow, argument)?,
+        };
+        if !values.is_empty() {
+            repeated.push((name.to_string(), values));
+        }
     }
+
+    for (name, values) in workflow.matrix() {
+        let values = match given(name) {
+            given if given.is_empty() => values.clone(),
+            given => given,
+        };
+        repeated.push((name.clone(), values));
+    }
+
+    Ok(repeated)
+}
+
+/// Prompt for the values of a repeated argument, picked among its `values` if it has any.
+fn prompt_values(workflow: &Workflow, argument: &Argument) -> Result<Vec<String>, Error> {
+    let name = argument.name().inner();
+    let values = match argument.values().is_empty() {
+        true => {
+            let recent = match argument.remember() {
+                true => STORE.recent_values(&workflow.id(), name)?,
+                false => Vec::new(),
+            };
+            let mut prompt = Text::new(name).with_help_message("Separate values with commas");
+            if let Some(default) = recent.first() {
+                prompt = prompt.with_default(default);
+            }
+            prompt
+                .prompt()
+                .map_err(|e| Error::ReadError(Some(e.into())))?
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .collect()
+        }
+        false => MultiSelect::new(
+            name,
+            argument
+                .values()
+                .iter()
+                .map(|value| value.inner().to_string())
+                .collect(),
+        )
+        .with_help_message(argument.def_description())
+        .prompt()
+        .map_err(|e| Error::ReadError(Some(e.into())))?,
+    };
+
+    Ok(values)
+}
+
+/// Every combination of the given values, the first argument varying the slowest. Without
+/// any repeated argument, the only combination is empty.
+pub fn combinations(repeated: &[(String, Vec<String>)]) -> Vec<Vec<(String, String)>> {
+    repeated
+        .iter()
+        .fold(vec![Vec::new()], |combinations, (name, values)| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((name.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect()
+        })
 }
 
 impl Parser for Argument {
@@ -81,7 +315,7 @@ impl Parser for Argument {
         let mut arguments = HashMap::new();
         let default_value = self
             .default()
-            .unwrap_or(&ArgumentDefault::new("<insert value>".into()))
+            .unwrap_or(&ArgumentDefault::new(PLACEHOLDER.into()))
             .inner()
             .to_string();
 
@@ -99,10 +333,31 @@ impl Parser for Argument {
     }
 }
 
+/// Parse a JSON object into a `Precedence`. Scalar values are converted to strings.
+fn parse_json_arguments(input: &str) -> Result<Precedence, Error> {
+    let object = serde_json::from_str::<HashMap<String, Value>>(input)
+        .map_err(|e| Error::InvalidArguments(Some(e.into())))?;
+
+    object
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => Ok((key, value)),
+            Value::Number(_) | Value::Bool(_) => Ok((key, value.to_string())),
+            _ => Err(Error::InvalidArguments(Some(
+                format!("The value of argument `{}` must be a string", key).into(),
+            ))),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::args::Argument;
+    use crate::domain::{
+        args::Argument,
+        command::{Cli, Command},
+    };
+    use clap::Parser as _;
 
     #[test]
     fn test_parse_argument() {
@@ -139,6 +394,143 @@ mod tests {
         assert_eq!(argument.get("test_arg"), Some(&"super test".to_string()));
     }
 
+    #[test]
+    fn test_resolve_repeated() {
+        let workflow = r#"
+            name: test
+            command: ./deploy.sh {{env}} {{region}}
+            arguments:
+              - name: env
+                repeat: true
+              - name: version
+            matrix:
+              region: [eu, us]
+        "#;
+        let workflow = serde_yaml::from_str::<Workflow>(workflow).unwrap();
+        let provided = vec![
+            ("env".to_string(), "staging".to_string()),
+            ("env".to_string(), "production".to_string()),
+            ("version".to_string(), "1.2".to_string()),
+        ];
+
+        let repeated = resolve_repeated(&workflow, &provided, false).unwrap();
+        assert_eq!(
+            repeated,
+            vec![
+                (
+                    "env".to_string(),
+                    vec!["staging".into(), "production".into()]
+                ),
+                ("region".to_string(), vec!["eu".into(), "us".into()]),
+            ]
+        );
+
+        let combinations = combinations(&repeated);
+        assert_eq!(combinations.len(), 4);
+        assert_eq!(
+            combinations[1],
+            vec![
+                ("env".to_string(), "staging".to_string()),
+                ("region".to_string(), "us".to_string()),
+            ]
+        );
+
+        // Values given on the command line replace the ones of the matrix
+        let provided = vec![("region".to_string(), "eu".to_string())];
+        let repeated = resolve_repeated(&workflow, &provided, false).unwrap();
+        assert_eq!(repeated, vec![("region".to_string(), vec!["eu".into()])]);
+        assert_eq!(super::combinations(&[]), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn test_parse_json_arguments() {
+        let precedence =
+            parse_json_arguments(r#"{"userName": "Jane Doe", "retries": 3, "verbose": true}"#)
+                .unwrap();
+
+        assert_eq!(precedence.len(), 3);
+        assert_eq!(precedence.get("userName"), Some(&"Jane Doe".to_string()));
+        assert_eq!(precedence.get("retries"), Some(&"3".to_string()));
+        assert_eq!(precedence.get("verbose"), Some(&"true".to_string()));
+    }
+
+    #[test]
+    fn test_parse_json_arguments_with_nested_value() {
+        let precedence = parse_json_arguments(r#"{"userName": ["Jane", "Doe"]}"#);
+
+        assert!(precedence.is_err());
+    }
+
+    #[test]
+    fn test_parse_run_arguments_take_precedence() {
+        let path = std::env::temp_dir().join("workflow_test_parse_run_arguments.json");
+        std::fs::write(
+            &path,
+            r#"{"userName": "Jane Doe", "userEmail": "jane@doe.com"}"#,
+        )
+        .unwrap();
+
+        let command = Cli::parse_from([
+            "workflow",
+            "run",
+            "--name",
+            "echo",
+            "--args-json",
+            path.to_str().unwrap(),
+            "--arg",
+            "userName=John Doe",
+        ])
+        .into_command();
+        let Command::Run(run) = command else {
+            panic!("Expected a run command");
+        };
+
+        let precedence = run.try_parse(()).unwrap();
+        std::fs::remove_file(&path).unwrap_or_default();
+
+        assert_eq!(precedence.get("userName"), Some(&"John Doe".to_string()));
+        assert_eq!(
+            precedence.get("userEmail"),
+            Some(&"jane@doe.com".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_workflow_with_all_arguments_provided() {
+        let arguments = vec![
+            Argument::slim("userName", None, vec![]),
+            Argument::slim("userEmail", Some("johndoe@example.com"), vec![]),
+        ];
+        let workflow = Workflow::slim("test", "echo {{userName}} {{userEmail}}", arguments);
+
+        let mut provided = HashMap::new();
+        provided.insert("userName".into(), "Jane Doe".into());
+        provided.insert("userEmail".into(), "jane@doe.com".into());
+
+        let precedence = workflow.try_parse(provided).unwrap();
+
+        assert_eq!(precedence.len(), 2);
+        assert_eq!(precedence.get("userName"), Some(&"Jane Doe".to_string()));
+        assert_eq!(
+            precedence.get("userEmail"),
+            Some(&"jane@doe.com".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_workflow_with_unknown_argument() {
+        let arguments = vec![Argument::slim("userName", None, vec![])];
+        let workflow = Workflow::slim("test", "echo {{userName}}", arguments);
+
+        let mut provided = HashMap::new();
+        provided.insert("userName".into(), "Jane Doe".into());
+        provided.insert("userAge".into(), "42".into());
+
+        let result = workflow.try_parse(provided);
+
+        assert!(matches!(result, Err(Error::InvalidArguments(_))));
+    }
+
     // Depends on https://github.com/mikaelmello/inquire/issues/70
     // #[test]
     // fn test_parse_workflow() {
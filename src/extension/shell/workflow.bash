@@ -0,0 +1,14 @@
+# Shell integration of `workflow` for bash, add `eval "$(workflow init bash)"` to ~/.bashrc
+#
+# Ctrl-G searches for a workflow and inserts its rendered command at the cursor. The command is
+# written to fd 3 while the prompts of `workflow` keep using the terminal.
+__workflow_widget() {
+  local command
+  command="$(workflow search --print 3 3>&1 1>/dev/tty </dev/tty)" || return
+  READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}${command}${READLINE_LINE:$READLINE_POINT}"
+  READLINE_POINT=$((READLINE_POINT + ${#command}))
+}
+
+bind -m emacs-standard -x '"\C-g": __workflow_widget'
+bind -m vi-command -x '"\C-g": __workflow_widget'
+bind -m vi-insert -x '"\C-g": __workflow_widget'
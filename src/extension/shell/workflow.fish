@@ -0,0 +1,16 @@
+# Shell integration of `workflow` for fish, add `workflow init fish | source` to ~/.config/fish/config.fish
+#
+# Ctrl-G searches for a workflow and inserts its rendered command at the cursor. The command is
+# written to fd 3 while the prompts of `workflow` keep using the terminal.
+function __workflow_widget
+    set -l command (workflow search --print 3 3>&1 1>/dev/tty </dev/tty | string collect)
+    if test -n "$command"
+        commandline --insert -- $command
+    end
+    commandline --function repaint
+end
+
+bind \cg __workflow_widget
+if bind -M insert >/dev/null 2>&1
+    bind -M insert \cg __workflow_widget
+end
@@ -1,8 +1,19 @@
-use std::{path::Path, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
-use crate::prelude::{Error, Unit, Workflow};
+use crate::prelude::{job_key, Error, HistoryEntry, Job, ScheduledRun, Unit, Workflow, WorkflowId};
 
-use rocksdb::{Direction, IteratorMode, WriteBatch, DB as RocksDB};
+use rocksdb::{Direction, IteratorMode, Options, WriteBatch, DB as RocksDB};
+
+/// The column family of the run history, kept apart so crawling never touches it.
+pub const HISTORY: &str = "history";
+/// The column family of the values recently given to the arguments of each workflow.
+pub const RECENT_VALUES: &str = "values";
+/// The column family of the jobs started with `workflow run --detach`.
+pub const JOBS: &str = "jobs";
+/// The column family of the outcome of the runs started by `workflow scheduler`.
+pub const SCHEDULED_RUNS: &str = "scheduled";
+/// How many values are remembered for each argument.
+const RECENT_VALUES_LIMIT: usize = 10;
 
 pub trait Store<T> {
     /// Initialize the store with the given file path.
@@ -24,6 +35,40 @@ pub trait Store<T> {
     fn delete_all(&mut self) -> Result<Unit, Error>;
 }
 
+pub trait HistoryStore {
+    /// Append the given entry to the history.
+    fn record(&self, entry: &HistoryEntry) -> Result<Unit, Error>;
+    /// Get every entry of the history, the most recent first.
+    fn history(&self) -> Result<Vec<HistoryEntry>, Error>;
+}
+
+pub trait JobStore {
+    /// Insert the given job, or update it if it is already known.
+    fn save_job(&self, job: &Job) -> Result<Unit, Error>;
+    /// Get the job with the given number.
+    fn job(&self, id: u64) -> Result<Option<Job>, Error>;
+    /// Get every job, the oldest first.
+    fn jobs(&self) -> Result<Vec<Job>, Error>;
+}
+
+pub trait ScheduleStore {
+    /// Record the outcome of a run started by `workflow scheduler`.
+    fn record_scheduled_run(&self, run: &ScheduledRun) -> Result<Unit, Error>;
+    /// The recorded scheduled runs, the most recent first.
+    fn scheduled_runs(&self) -> Result<Vec<ScheduledRun>, Error>;
+}
+
+pub trait RecentValueStore {
+    /// Put the given values first in the recent values of the arguments of the given workflow.
+    fn remember(
+        &self,
+        workflow: &WorkflowId,
+        values: &HashMap<String, String>,
+    ) -> Result<Unit, Error>;
+    /// Get the recent values of an argument of the given workflow, the most recent first.
+    fn recent_values(&self, workflow: &WorkflowId, argument: &str) -> Result<Vec<String>, Error>;
+}
+
 #[derive(Clone)]
 pub struct WorkStore {
     db: Arc<RocksDB>,
@@ -41,9 +86,17 @@ impl Store<Workflow> for WorkStore {
     where
         Self: Sized,
     {
-        RocksDB::open_default(file_path)
-            .map_err(|e| Error::StoreError(Some(e.into())))
-            .map(WorkStore::new)
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        RocksDB::open_cf(
+            &options,
+            file_path,
+            [HISTORY, RECENT_VALUES, JOBS, SCHEDULED_RUNS],
+        )
+        .map_err(|e| Error::StoreError(Some(e.into())))
+        .map(WorkStore::new)
     }
 
     fn insert_all(&mut self, data: Vec<Workflow>) -> Result<Unit, Error> {
@@ -142,10 +195,172 @@ impl Store<Workflow> for WorkStore {
     }
 }
 
+impl HistoryStore for WorkStore {
+    fn record(&self, entry: &HistoryEntry) -> Result<Unit, Error> {
+        let history = self.db.cf_handle(HISTORY).ok_or(Error::StoreError(Some(
+            "The history column family is missing".into(),
+        )))?;
+        let v = serde_json::to_string(entry).map_err(|e| Error::StoreError(Some(e.into())))?;
+
+        self.db
+            .put_cf(history, entry.key().as_bytes(), v.as_bytes())
+            .map_err(|e| Error::StoreError(Some(e.into())))
+    }
+
+    fn history(&self) -> Result<Vec<HistoryEntry>, Error> {
+        let history = self.db.cf_handle(HISTORY).ok_or(Error::StoreError(Some(
+            "The history column family is missing".into(),
+        )))?;
+
+        self.db
+            .iterator_cf(history, IteratorMode::End)
+            .map(|v| {
+                let (_, v) = v.map_err(|e| Error::StoreError(Some(e.into())))?;
+                serde_json::from_slice::<HistoryEntry>(&v)
+                    .map_err(|e| Error::StoreError(Some(e.into())))
+            })
+            .collect()
+    }
+}
+
+impl JobStore for WorkStore {
+    fn save_job(&self, job: &Job) -> Result<Unit, Error> {
+        let jobs = self.db.cf_handle(JOBS).ok_or(Error::StoreError(Some(
+            "The jobs column family is missing".into(),
+        )))?;
+        let v = serde_json::to_string(job).map_err(|e| Error::StoreError(Some(e.into())))?;
+
+        self.db
+            .put_cf(jobs, job.key().as_bytes(), v.as_bytes())
+            .map_err(|e| Error::StoreError(Some(e.into())))
+    }
+
+    fn job(&self, id: u64) -> Result<Option<Job>, Error> {
+        let jobs = self.db.cf_handle(JOBS).ok_or(Error::StoreError(Some(
+            "The jobs column family is missing".into(),
+        )))?;
+
+        match self
+            .db
+            .get_cf(jobs, job_key(id).as_bytes())
+            .map_err(|e| Error::StoreError(Some(e.into())))?
+        {
+            Some(v) => serde_json::from_slice::<Job>(&v)
+                .map(Some)
+                .map_err(|e| Error::StoreError(Some(e.into()))),
+            None => Ok(None),
+        }
+    }
+
+    fn jobs(&self) -> Result<Vec<Job>, Error> {
+        let jobs = self.db.cf_handle(JOBS).ok_or(Error::StoreError(Some(
+            "The jobs column family is missing".into(),
+        )))?;
+
+        self.db
+            .iterator_cf(jobs, IteratorMode::Start)
+            .map(|v| {
+                let (_, v) = v.map_err(|e| Error::StoreError(Some(e.into())))?;
+                serde_json::from_slice::<Job>(&v).map_err(|e| Error::StoreError(Some(e.into())))
+            })
+            .collect()
+    }
+}
+
+impl ScheduleStore for WorkStore {
+    fn record_scheduled_run(&self, run: &ScheduledRun) -> Result<Unit, Error> {
+        let runs = self
+            .db
+            .cf_handle(SCHEDULED_RUNS)
+            .ok_or(Error::StoreError(Some(
+                "The scheduled runs column family is missing".into(),
+            )))?;
+        let v = serde_json::to_string(run).map_err(|e| Error::StoreError(Some(e.into())))?;
+
+        self.db
+            .put_cf(runs, run.key().as_bytes(), v.as_bytes())
+            .map_err(|e| Error::StoreError(Some(e.into())))
+    }
+
+    fn scheduled_runs(&self) -> Result<Vec<ScheduledRun>, Error> {
+        let runs = self
+            .db
+            .cf_handle(SCHEDULED_RUNS)
+            .ok_or(Error::StoreError(Some(
+                "The scheduled runs column family is missing".into(),
+            )))?;
+
+        self.db
+            .iterator_cf(runs, IteratorMode::End)
+            .map(|v| {
+                let (_, v) = v.map_err(|e| Error::StoreError(Some(e.into())))?;
+                serde_json::from_slice::<ScheduledRun>(&v)
+                    .map_err(|e| Error::StoreError(Some(e.into())))
+            })
+            .collect()
+    }
+}
+
+impl RecentValueStore for WorkStore {
+    fn remember(
+        &self,
+        workflow: &WorkflowId,
+        values: &HashMap<String, String>,
+    ) -> Result<Unit, Error> {
+        let recent = self
+            .db
+            .cf_handle(RECENT_VALUES)
+            .ok_or(Error::StoreError(Some(
+                "The recent values column family is missing".into(),
+            )))?;
+
+        let mut db_batch = WriteBatch::default();
+        values.iter().try_for_each(|(argument, value)| {
+            let mut values = self.recent_values(workflow, argument)?;
+            values.retain(|known| known != value);
+            values.insert(0, value.to_owned());
+            values.truncate(RECENT_VALUES_LIMIT);
+
+            let v =
+                serde_json::to_string(&values).map_err(|e| Error::StoreError(Some(e.into())))?;
+            db_batch.put_cf(recent, recent_key(workflow, argument), v.as_bytes());
+            Ok::<Unit, Error>(())
+        })?;
+
+        self.db
+            .write(db_batch)
+            .map_err(|e| Error::StoreError(Some(e.into())))
+    }
+
+    fn recent_values(&self, workflow: &WorkflowId, argument: &str) -> Result<Vec<String>, Error> {
+        let recent = self
+            .db
+            .cf_handle(RECENT_VALUES)
+            .ok_or(Error::StoreError(Some(
+                "The recent values column family is missing".into(),
+            )))?;
+
+        match self
+            .db
+            .get_cf(recent, recent_key(workflow, argument))
+            .map_err(|e| Error::StoreError(Some(e.into())))?
+        {
+            Some(v) => serde_json::from_slice::<Vec<String>>(&v)
+                .map_err(|e| Error::StoreError(Some(e.into()))),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// The key of the recent values of an argument, unique as neither part contains a NUL byte.
+fn recent_key(workflow: &WorkflowId, argument: &str) -> Vec<u8> {
+    format!("{}\0{}", workflow.inner(), argument).into_bytes()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prelude::Argument;
+    use crate::prelude::{Argument, WorkflowId};
 
     pub const WORKFLOW: &str = {
         #[cfg(target_os = "windows")]
@@ -199,4 +414,130 @@ mod tests {
 
         std::fs::remove_dir_all(&path).unwrap_or_default();
     }
+
+    #[test]
+    fn test_history() {
+        let path = Path::new(WORKFLOW).join("test_history.db");
+        std::fs::create_dir(&path).unwrap_or_default();
+
+        let mut store = WorkStore::init(&path).unwrap();
+        let entry = |workflow: &str, seconds: i64, exit_code: i32| {
+            HistoryEntry::new(
+                WorkflowId::from(workflow),
+                &std::collections::HashMap::from([("name".to_string(), "value".to_string())]),
+                "echo value",
+                Some(Path::new("/tmp")),
+                chrono::DateTime::from_timestamp(seconds, 0).unwrap(),
+                std::time::Duration::from_millis(1500),
+                exit_code,
+            )
+        };
+
+        store.record(&entry("echo", 20, 0)).unwrap();
+        store.record(&entry("deploy", 10, 1)).unwrap();
+        store
+            .insert_all(vec![Workflow::slim("test", "test", Vec::new())])
+            .unwrap();
+        store.delete_all().unwrap();
+
+        let history = store.history().unwrap();
+        std::fs::remove_dir_all(&path).unwrap_or_default();
+
+        assert_eq!(history, vec![entry("echo", 20, 0), entry("deploy", 10, 1)]);
+    }
+
+    #[test]
+    fn test_recent_values() {
+        let path = Path::new(WORKFLOW).join("test_recent_values.db");
+        std::fs::create_dir(&path).unwrap_or_default();
+
+        let store = WorkStore::init(&path).unwrap();
+        let workflow = WorkflowId::from("deploy");
+        let values = |branch: &str| HashMap::from([("branch".to_string(), branch.to_string())]);
+
+        store.remember(&workflow, &values("main")).unwrap();
+        store.remember(&workflow, &values("develop")).unwrap();
+        store.remember(&workflow, &values("main")).unwrap();
+        (0..RECENT_VALUES_LIMIT).for_each(|index| {
+            store
+                .remember(&WorkflowId::from("other"), &values(&index.to_string()))
+                .unwrap()
+        });
+
+        let recent = store.recent_values(&workflow, "branch").unwrap();
+        let other = store
+            .recent_values(&WorkflowId::from("other"), "branch")
+            .unwrap();
+        let unknown = store.recent_values(&workflow, "tag").unwrap();
+        std::fs::remove_dir_all(&path).unwrap_or_default();
+
+        assert_eq!(recent, vec!["main", "develop"]);
+        assert_eq!(other.len(), RECENT_VALUES_LIMIT);
+        assert_eq!(other[0], "9");
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn test_scheduled_runs() {
+        let path = Path::new(WORKFLOW).join("test_scheduled_runs.db");
+        std::fs::create_dir(&path).unwrap_or_default();
+
+        let store = WorkStore::init(&path).unwrap();
+        let run = |job: u64, seconds: i64, exit_code: i32| {
+            ScheduledRun::new(
+                WorkflowId::from("backup"),
+                serde_yaml::from_str("{ cron: 0 2 * * *, arguments: { target: production } }")
+                    .unwrap(),
+                &HashMap::from([("target".to_string(), "production".to_string())]),
+                job,
+                chrono::DateTime::from_timestamp(seconds, 0).unwrap(),
+                std::time::Duration::from_secs(3),
+                exit_code,
+                Path::new("/tmp/jobs/1"),
+            )
+        };
+
+        store.record_scheduled_run(&run(1, 10, 0)).unwrap();
+        store.record_scheduled_run(&run(2, 20, 1)).unwrap();
+
+        let runs = store.scheduled_runs().unwrap();
+        std::fs::remove_dir_all(&path).unwrap_or_default();
+
+        assert_eq!(runs, vec![run(2, 20, 1), run(1, 10, 0)]);
+    }
+
+    #[test]
+    fn test_jobs() {
+        let path = Path::new(WORKFLOW).join("test_jobs.db");
+        std::fs::create_dir(&path).unwrap_or_default();
+
+        let store = WorkStore::init(&path).unwrap();
+        let job = |id: u64| {
+            Job::new(
+                id,
+                Workflow::slim("deploy", "./deploy.sh", Vec::new()),
+                &HashMap::new(),
+                "./deploy.sh",
+                chrono::Utc::now(),
+            )
+        };
+
+        store.save_job(&job(2)).unwrap();
+        store.save_job(&job(10)).unwrap();
+        store.save_job(&job(2).with_pid(42)).unwrap();
+
+        let ids = store
+            .jobs()
+            .unwrap()
+            .iter()
+            .map(Job::id)
+            .collect::<Vec<u64>>();
+        let updated = store.job(2).unwrap().and_then(|job| job.pid());
+        let unknown = store.job(3).unwrap();
+        std::fs::remove_dir_all(&path).unwrap_or_default();
+
+        assert_eq!(ids, vec![2, 10]);
+        assert_eq!(updated, Some(42));
+        assert!(unknown.is_none());
+    }
 }
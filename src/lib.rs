@@ -3,6 +3,7 @@ pub mod domain;
 pub mod extension;
 
 pub mod prelude {
+    use std::error::Error as StdError;
     use std::path::{Path, PathBuf};
     use std::str::FromStr;
 
@@ -15,43 +16,97 @@ pub mod prelude {
     #[doc(inline)]
     pub use crate::extension::prelude::*;
 
-    pub static WORKDIR: Lazy<PathBuf> = Lazy::new(|| {
-        let home_env_var = {
-            #[cfg(not(target_os = "windows"))]
-            {
-                env!("HOME")
-            }
-            #[cfg(target_os = "windows")]
-            {
-                env!("USERPROFILE")
+    /// The home directory of the user, as known when the binary was built.
+    pub fn home_dir() -> &'static str {
+        #[cfg(not(target_os = "windows"))]
+        {
+            env!("HOME")
+        }
+        #[cfg(target_os = "windows")]
+        {
+            env!("USERPROFILE")
+        }
+    }
+
+    /// The configuration file, if there is one, or why it is invalid. An invalid file is not
+    /// ignored, since its `workflow_dir` and `dangerous_pattern` would silently be left out.
+    pub static CONFIGURATION: Lazy<Result<Option<Configuration>, String>> = Lazy::new(|| {
+        let path = Path::new(home_dir()).join(".config").join("workflows");
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read configuration file `{}`: {}",
+                    path.display(),
+                    e
+                ))
             }
         };
 
-        let path = Path::new(&home_env_var).join(".config").join("workflows");
-        let configuration: Result<Result<Configuration, Error>, Error> =
-            std::fs::read_to_string(path)
-                .map(|s| Configuration::from_str(&s))
-                .map_err(|e| {
-                    Error::InvalidConfiguration(Some(
-                        format!("Failed to read configuration file: {}", e).into(),
-                    ))
-                });
+        Configuration::from_str(&content).map(Some).map_err(|e| {
+            let cause = e.source().map(ToString::to_string).unwrap_or(e.to_string());
+            format!("{} in `{}`", cause, path.display())
+        })
+    });
+
+    /// The configuration file, if there is one.
+    ///
+    /// # Returns
+    /// * The configuration or an `Error` if the file is invalid
+    pub fn configuration() -> Result<Option<&'static Configuration>, Error> {
+        match CONFIGURATION.as_ref() {
+            Ok(configuration) => Ok(configuration.as_ref()),
+            Err(message) => Err(Error::InvalidConfiguration(Some(message.clone().into()))),
+        }
+    }
 
+    pub static WORKDIR: Lazy<PathBuf> = Lazy::new(|| {
         let home = std::env::var("WORKFLOW_DIR").map(|s| Path::new(&s).to_path_buf());
         match home {
             Ok(home) => home,
-            Err(_) => match configuration {
-                Ok(Ok(path)) => Path::new(path.workflow_dir()).to_path_buf(),
-                _ => Path::new(home_env_var).join(".workflows"),
+            Err(_) => match configuration().ok().flatten() {
+                Some(configuration) => Path::new(configuration.workflow_dir()).to_path_buf(),
+                None => Path::new(home_dir()).join(".workflows"),
             },
         }
     });
 
+    /// The shell of the workflows that do not set their own, `sh` unless configured otherwise.
+    pub static SHELL: Lazy<WorkflowShell> = Lazy::new(|| {
+        configuration()
+            .ok()
+            .flatten()
+            .and_then(|configuration| configuration.shell())
+            .copied()
+            .unwrap_or_default()
+    });
+
+    /// Detects dangerous commands, with the patterns of the configuration file on top of the built-in ones.
+    pub static DETECTOR: Lazy<DangerDetector> = Lazy::new(|| {
+        DangerDetector::new(
+            configuration()
+                .ok()
+                .flatten()
+                .map(|configuration| configuration.dangerous_patterns())
+                .unwrap_or_default(),
+        )
+    });
+
     // This is fine because a CLI application is a blocking application. Hence, we can use a global
     // variable to store the index, writer, and reader.
     pub const INDEX_DIR: &str = "index";
+    /// The directory of the jobs started with `workflow run --detach`, one directory per job
+    /// holding its output.
+    pub const JOBS_DIR: &str = "jobs";
     pub static STORE: Lazy<WorkStore> =
         Lazy::new(|| WorkStore::init(&WORKDIR.join(INDEX_DIR)).expect("Failed to create store"));
 
+    /// The directory of the helpers of the templates written by the user, e.g. `slug.rhai` for
+    /// `{{slug name}}`.
+    pub const HELPERS_DIR: &str = "helpers";
+    pub static SCRIPT_HELPERS: Lazy<ScriptHelpers> =
+        Lazy::new(|| ScriptHelpers::new(&WORKDIR.join(HELPERS_DIR)));
+
     pub type Unit = ();
 }
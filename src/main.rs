@@ -1,32 +1,92 @@
+use std::io::Write;
+
 use clap::Parser;
 use workflow::prelude::*;
 
-fn main() -> Result<Unit, Error> {
-    // Crawls the current directory for workflow files
-    Crawler::crawl(&WORKDIR, &STORE)?;
+fn main() {
+    // Parse the command line arguments. A usage error exits with `EX_USAGE`, as invalid arguments
+    // do, rather than with `2`, which the executed command could exit with as well.
+    let cli = Cli::try_parse().unwrap_or_else(|error| {
+        let code = match error.use_stderr() {
+            true => Error::InvalidArguments(None).exit_code(),
+            // `--help` and `--version`
+            false => 0,
+        };
+        let _ = error.print();
+        std::process::exit(code);
+    });
+    // With `--output json` stdout is kept for the document, anything else is written to stderr
+    let document = match cli.output() {
+        OutputFormat::Json => Some(reserve_stdout()),
+        OutputFormat::Text => None,
+    };
+    let result = run(cli);
+
+    if let Err(error) = &result {
+        eprintln!("Error: {:?}", error);
+    }
+    if let Some(document) = document {
+        let json = match &result {
+            Ok(output) => output.to_json(),
+            Err(error) => error.to_json(),
+        };
+        if let Err(error) = document.and_then(|mut document| {
+            writeln!(document, "{}", json).map_err(|e| Error::Io(Some(e.into())))
+        }) {
+            eprintln!("Error: {:?}", error);
+        }
+    }
+
+    // Exits with the code of the executed command, or the one of the error that aborted the CLI.
+    let code = match result {
+        Ok(output) => output.exit_code(),
+        Err(error) => error.exit_code(),
+    };
+
+    std::process::exit(code);
+}
+
+fn run(cli: Cli) -> Result<Output, Error> {
+    let yes = cli.yes();
+    let command = cli.into_command();
+    // An invalid configuration file fails every command rather than being ignored
+    configuration()?;
 
-    // Parse the command line arguments.
-    let command: Command = Command::parse();
+    let needs_store = match &command {
+        // A detached job has its workflow at hand, so it only opens the store once it is done
+        Command::RunJob(_) => false,
+        // The scheduler runs until stopped, so it only opens the store for a moment at a time
+        Command::Daemon(daemon) => daemon.list(),
+        _ => true,
+    };
+    if needs_store {
+        // Crawls the current directory for workflow files
+        Crawler::crawl(&WORKDIR, &STORE)?;
+    }
 
     // Execute the command.
     match command {
         Command::Run(command) => {
+            let command = command.with_yes(yes);
             let workflow = command.prepare()?;
-            command.execute(workflow)?;
-        }
-        Command::List(command) => {
-            command.execute(())?;
+            command.execute(workflow)
         }
-        Command::Search(command) => {
-            command.execute(())?;
-        }
-        Command::Reset(command) => {
-            command.execute(())?;
-        }
-        Command::Create(command) => {
-            command.execute(())?;
+        Command::Pipe(command) => {
+            let command = command.with_yes(yes);
+            let workflows = command.prepare()?;
+            command.execute(workflows)
         }
+        Command::List(command) => command.execute(()),
+        Command::Search(command) => command.with_yes(yes).execute(()),
+        Command::Reset(command) => command.with_yes(yes).execute(()),
+        Command::Create(command) => command.execute(()),
+        Command::Init(command) => command.execute(()),
+        Command::History(command) => command.execute(()),
+        Command::Rerun(command) => command.with_yes(yes).execute(()),
+        Command::Jobs(command) => command.execute(()),
+        Command::Logs(command) => command.execute(()),
+        Command::Kill(command) => command.execute(()),
+        Command::Daemon(command) => command.execute(()),
+        Command::RunJob(command) => command.execute(()),
     }
-
-    Ok(())
 }